@@ -0,0 +1,505 @@
+use crate::color::Color;
+use crate::document::Document;
+use crate::palette::Palette;
+use crate::selection::{self, Selection};
+
+type Pixels = Vec<Vec<(u8, u8, u8, u8)>>;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Adjustment
+{
+    HueSaturation,
+    BrightnessContrast,
+    Levels,
+    Curves,
+    Posterize,
+    Invert,
+    Desaturate,
+    Colorize,
+}
+
+impl Adjustment
+{
+    pub const ALL: [Adjustment; 8] = [Adjustment::HueSaturation, Adjustment::BrightnessContrast, Adjustment::Levels, Adjustment::Curves, Adjustment::Posterize, Adjustment::Invert, Adjustment::Desaturate, Adjustment::Colorize];
+
+    pub fn name(self) -> &'static str
+    {
+        match self
+        {
+            Adjustment::HueSaturation => "Hue / Saturation",
+            Adjustment::BrightnessContrast => "Brightness / Contrast",
+            Adjustment::Levels => "Levels",
+            Adjustment::Curves => "Curves",
+            Adjustment::Posterize => "Posterize",
+            Adjustment::Invert => "Invert",
+            Adjustment::Desaturate => "Desaturate",
+            Adjustment::Colorize => "Colorize",
+        }
+    }
+}
+
+//Values of all adjustments, only the ones of the chosen adjustment are used
+#[derive(Clone, PartialEq, Debug)]
+pub struct AdjustParams
+{
+    pub hue: f32,//Degrees
+    pub saturation: f32,//-1..1
+    pub lightness: f32,//-1..1
+    pub brightness: f32,
+    pub contrast: f32,
+    pub in_black: f32,
+    pub in_white: f32,
+    pub gamma: f32,
+    pub out_black: f32,
+    pub out_white: f32,
+    pub curve: Vec<(f32, f32)>,//Sorted by x, first and last point stay at x 0 and 1
+    pub posterize: u32,
+    pub colorize_hue: f32,
+    pub colorize_saturation: f32,
+}
+
+impl Default for AdjustParams
+{
+    fn default() -> Self
+    {
+        AdjustParams
+        {
+            hue: 0.0,
+            saturation: 0.0,
+            lightness: 0.0,
+            brightness: 0.0,
+            contrast: 0.0,
+            in_black: 0.0,
+            in_white: 1.0,
+            gamma: 1.0,
+            out_black: 0.0,
+            out_white: 1.0,
+            curve: vec![(0.0, 0.0), (1.0, 1.0)],
+            posterize: 4,
+            colorize_hue: 30.0,
+            colorize_saturation: 0.5,
+        }
+    }
+}
+
+//Piecewise linear curve through the points
+fn curve_value(curve: &[(f32, f32)], x: f32) -> f32
+{
+    for pair in curve.windows(2)
+    {
+        let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+        if x <= x1
+        {
+            let t = if x1 > x0 { (x - x0) / (x1 - x0) } else { 0.0 };
+            return y0 + (y1 - y0) * t.clamp(0.0, 1.0);
+        }
+    }
+    curve.last().map(|p| p.1).unwrap_or(x)
+}
+
+pub fn adjust_color(color: Color, kind: Adjustment, params: &AdjustParams) -> Color
+{
+    let rgb = |f: &dyn Fn(f32) -> f32| Color::new(f(color.r), f(color.g), f(color.b), color.a).clamped();
+
+    match kind
+    {
+        Adjustment::HueSaturation =>
+        {
+            let [h, s, l, a] = color.to_hsl();
+            let l = if params.lightness > 0.0 { l + (1.0 - l) * params.lightness } else { l * (1.0 + params.lightness) };
+            Color::from_hsl([h + params.hue, (s * (1.0 + params.saturation)).clamp(0.0, 1.0), l, a])
+        }
+        Adjustment::BrightnessContrast =>
+        {
+            //Contrast -1..1 maps to a slope of 0..infinity around middle grey
+            let slope = ((params.contrast.clamp(-1.0, 0.999) + 1.0) * std::f32::consts::FRAC_PI_4).tan();
+            rgb(&|c| (c - 0.5) * slope + 0.5 + params.brightness)
+        }
+        Adjustment::Levels =>
+        {
+            let range = (params.in_white - params.in_black).max(0.0001);
+            rgb(&|c|
+            {
+                let v = ((c - params.in_black) / range).clamp(0.0, 1.0).powf(1.0 / params.gamma);
+                params.out_black + v * (params.out_white - params.out_black)
+            })
+        }
+        Adjustment::Curves => rgb(&|c| curve_value(&params.curve, c)),
+        Adjustment::Posterize =>
+        {
+            let steps = (params.posterize.max(2) - 1) as f32;
+            rgb(&|c| (c * steps).round() / steps)
+        }
+        Adjustment::Invert => rgb(&|c| 1.0 - c),
+        Adjustment::Desaturate =>
+        {
+            let l = color.to_oklab()[0];
+            Color::from_oklab([l, 0.0, 0.0, color.a])
+        }
+        Adjustment::Colorize =>
+        {
+            let l = color.to_hsl()[2];
+            Color::from_hsl([params.colorize_hue, params.colorize_saturation, l, color.a])
+        }
+    }
+}
+
+//Adjusted copy of pixels, only inside of the selection, optionally snapped to the palette
+pub fn adjust_pixels(pixels: &Pixels, kind: Adjustment, params: &AdjustParams, selection: &Option<Selection>, palette: Option<&Palette>) -> Pixels
+{
+    let mut result = pixels.clone();
+
+    for (y, row) in result.iter_mut().enumerate()
+    {
+        for (x, pixel) in row.iter_mut().enumerate()
+        {
+            if pixel.3 == 0 || !selection::editable(selection, x as i32, y as i32)
+            {
+                continue;
+            }
+
+            let mut color = adjust_color(Color::from_u8(*pixel), kind, params);
+            if let Some(nearest) = palette.and_then(|p| p.nearest(color))
+            {
+                color = nearest;
+            }
+            *pixel = color.to_u8();
+        }
+    }
+
+    result
+}
+
+pub struct Adjustments
+{
+    pub open: bool,
+    pub kind: Adjustment,
+    pub params: AdjustParams,
+    pub snap_to_palette: bool,
+    preview: Option<(u64, Pixels, Option<Vec<bool>>)>,//Document id, pixels and selection the preview was made from
+    dirty: bool,//Preview has to be made again
+    curve_drag: Option<usize>,
+}
+
+impl Default for Adjustments
+{
+    fn default() -> Self
+    {
+        Adjustments
+        {
+            open: false,
+            kind: Adjustment::HueSaturation,
+            params: AdjustParams::default(),
+            snap_to_palette: false,
+            preview: None,
+            dirty: true,
+            curve_drag: None,
+        }
+    }
+}
+
+impl Adjustments
+{
+    //Shows the real pixels again, they were never changed
+    fn cancel(&mut self, documents: &mut [Document])
+    {
+        if let Some((id, _, _)) = self.preview.take()
+        {
+            if let Some(doc) = documents.iter_mut().find(|d| d.id == id)
+            {
+                doc.preview = None;
+                doc.canvas.mark_all_dirty();
+            }
+        }
+    }
+
+    //Made from the current pixels, so edits while the window is open are kept
+    fn apply(&mut self, doc: &mut Document)
+    {
+        self.preview = None;
+        doc.preview = None;
+        doc.checkpoint();
+        let palette = if self.snap_to_palette { Some(&doc.palette) } else { None };
+        doc.pixels = adjust_pixels(&doc.pixels, self.kind, &self.params, &doc.selection, palette);
+        doc.canvas.mark_all_dirty();
+    }
+}
+
+//The preview only goes into the display buffer of the document, Apply changes the pixels as one undo step
+pub fn adjustments_window(egui_ctx: &egui::Context, adjustments: &mut Adjustments, documents: &mut [Document], current: usize)
+{
+    if !adjustments.open
+    {
+        adjustments.cancel(documents);
+        adjustments.dirty = true;
+        return;
+    }
+
+    //Switched to another tab
+    if adjustments.preview.as_ref().map(|p| p.0) != documents.get(current).map(|d| d.id) && adjustments.preview.is_some()
+    {
+        adjustments.cancel(documents);
+        adjustments.dirty = true;
+    }
+    let doc = match documents.get_mut(current)
+    {
+        Some(doc) => doc,
+        None => return,
+    };
+
+    let before = (adjustments.kind, adjustments.params.clone(), adjustments.snap_to_palette);
+    let mut open = true;
+    let mut action = None;
+    egui::Window::new("Adjustments").open(&mut open).show(egui_ctx, |ui|
+    {
+        egui::ComboBox::from_id_source("adjustment").selected_text(adjustments.kind.name()).show_ui(ui, |ui|
+        {
+            for kind in Adjustment::ALL
+            {
+                ui.selectable_value(&mut adjustments.kind, kind, kind.name());
+            }
+        });
+
+        let params = &mut adjustments.params;
+        match adjustments.kind
+        {
+            Adjustment::HueSaturation =>
+            {
+                ui.add(egui::Slider::new(&mut params.hue, -180.0..=180.0).text("Hue"));
+                ui.add(egui::Slider::new(&mut params.saturation, -1.0..=1.0).text("Saturation"));
+                ui.add(egui::Slider::new(&mut params.lightness, -1.0..=1.0).text("Lightness"));
+            }
+            Adjustment::BrightnessContrast =>
+            {
+                ui.add(egui::Slider::new(&mut params.brightness, -1.0..=1.0).text("Brightness"));
+                ui.add(egui::Slider::new(&mut params.contrast, -1.0..=1.0).text("Contrast"));
+            }
+            Adjustment::Levels =>
+            {
+                ui.add(egui::Slider::new(&mut params.in_black, 0.0..=1.0).text("Input Black"));
+                ui.add(egui::Slider::new(&mut params.in_white, 0.0..=1.0).text("Input White"));
+                ui.add(egui::Slider::new(&mut params.gamma, 0.1..=10.0).logarithmic(true).text("Gamma"));
+                ui.add(egui::Slider::new(&mut params.out_black, 0.0..=1.0).text("Output Black"));
+                ui.add(egui::Slider::new(&mut params.out_white, 0.0..=1.0).text("Output White"));
+            }
+            Adjustment::Curves =>
+            {
+                ui.label("Drag points, click to add, right click to remove");
+                curve_editor(ui, &mut params.curve, &mut adjustments.curve_drag);
+            }
+            Adjustment::Posterize =>
+            {
+                ui.add(egui::Slider::new(&mut params.posterize, 2..=32).text("Levels"));
+            }
+            Adjustment::Invert | Adjustment::Desaturate => {}
+            Adjustment::Colorize =>
+            {
+                ui.add(egui::Slider::new(&mut params.colorize_hue, 0.0..=360.0).text("Hue"));
+                ui.add(egui::Slider::new(&mut params.colorize_saturation, 0.0..=1.0).text("Saturation"));
+            }
+        }
+
+        ui.checkbox(&mut adjustments.snap_to_palette, "Snap to palette");
+        if doc.selection.is_some()
+        {
+            ui.label("Only the selection is changed");
+        }
+        ui.horizontal(|ui|
+        {
+            if ui.button("Apply").clicked()
+            {
+                action = Some(true);
+            }
+            if ui.button("Cancel").clicked()
+            {
+                action = Some(false);
+            }
+            if ui.button("Reset").clicked()
+            {
+                adjustments.params = AdjustParams::default();
+            }
+        });
+    });
+
+    if (adjustments.kind, adjustments.params.clone(), adjustments.snap_to_palette) != before
+    {
+        adjustments.dirty = true;
+    }
+
+    match action
+    {
+        Some(true) =>
+        {
+            adjustments.apply(doc);
+            adjustments.open = false;
+        }
+        Some(false) | None if !open || action.is_some() =>
+        {
+            adjustments.cancel(std::slice::from_mut(doc));
+            adjustments.open = false;
+        }
+        //Painting, undo, resizing or selecting changed what the preview was made from
+        _ if adjustments.dirty || adjustments.preview.as_ref().map(|p| p.1 != doc.pixels || p.2 != doc.selection.as_ref().map(|s| s.mask.clone())).unwrap_or(true) =>
+        {
+            let palette = if adjustments.snap_to_palette { Some(&doc.palette) } else { None };
+            doc.preview = Some(adjust_pixels(&doc.pixels, adjustments.kind, &adjustments.params, &doc.selection, palette));
+            adjustments.preview = Some((doc.id, doc.pixels.clone(), doc.selection.as_ref().map(|s| s.mask.clone())));
+            doc.canvas.mark_all_dirty();
+            adjustments.dirty = false;
+        }
+        _ => {}
+    }
+}
+
+const CURVE_SIZE: f32 = 160.0;
+
+fn curve_editor(ui: &mut egui::Ui, curve: &mut Vec<(f32, f32)>, dragging: &mut Option<usize>)
+{
+    let (rect, response) = ui.allocate_exact_size(egui::vec2(CURVE_SIZE, CURVE_SIZE), egui::Sense::click_and_drag());
+    let to_screen = |p: (f32, f32)| egui::pos2(rect.min.x + p.0 * rect.width(), rect.max.y - p.1 * rect.height());
+    let from_screen = |p: egui::Pos2| (((p.x - rect.min.x) / rect.width()).clamp(0.0, 1.0), ((rect.max.y - p.y) / rect.height()).clamp(0.0, 1.0));
+    let near = |curve: &[(f32, f32)], p: egui::Pos2| curve.iter().position(|c| to_screen(*c).distance(p) < 6.0);
+
+    if let Some(pos) = response.interact_pointer_pos()
+    {
+        if response.drag_started()
+        {
+            *dragging = near(curve, pos);
+        }
+        if response.clicked() && near(curve, pos).is_none()
+        {
+            let p = from_screen(pos);
+            let i = curve.iter().position(|c| c.0 > p.0).unwrap_or(curve.len() - 1).max(1);
+            curve.insert(i, p);
+        }
+        if response.secondary_clicked()
+        {
+            if let Some(i) = near(curve, pos).filter(|i| *i > 0 && *i < curve.len() - 1)
+            {
+                curve.remove(i);
+            }
+        }
+        if let (Some(i), true) = (*dragging, response.dragged())
+        {
+            let p = from_screen(pos);
+            //End points only move up and down, the others stay between their neighbours
+            let x = if i == 0 { 0.0 } else if i == curve.len() - 1 { 1.0 } else { p.0.clamp(curve[i - 1].0, curve[i + 1].0) };
+            curve[i] = (x, p.1);
+        }
+    }
+    if !response.dragged()
+    {
+        *dragging = None;
+    }
+
+    let painter = ui.painter();
+    painter.rect_filled(rect, 0.0, egui::Color32::from_gray(30));
+    painter.line_segment([to_screen((0.0, 0.0)), to_screen((1.0, 1.0))], egui::Stroke::new(1.0, egui::Color32::from_gray(70)));
+    let points: Vec<egui::Pos2> = curve.iter().map(|p| to_screen(*p)).collect();
+    painter.add(egui::Shape::line(points.clone(), egui::Stroke::new(1.5, egui::Color32::WHITE)));
+    for p in points
+    {
+        painter.circle_filled(p, 3.5, egui::Color32::LIGHT_BLUE);
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    //Every channel value on a coarse grid, alpha varies too
+    fn image() -> Pixels
+    {
+        let steps = [0u8, 1, 17, 64, 127, 128, 200, 254, 255];
+        steps.iter().map(|r| steps.iter().flat_map(|g| steps.iter().map(move |b| (*r, *g, *b, 255 - *b / 2))).collect()).collect()
+    }
+
+    fn palette(colors: &[(u8, u8, u8, u8)]) -> Palette
+    {
+        Palette { colors: colors.iter().map(|c| Color::from_u8(*c)).collect(), ..Default::default() }
+    }
+
+    #[test]
+    fn default_params_change_nothing()
+    {
+        let pixels = image();
+        for kind in [Adjustment::HueSaturation, Adjustment::BrightnessContrast, Adjustment::Levels, Adjustment::Curves]
+        {
+            assert!(adjust_pixels(&pixels, kind, &AdjustParams::default(), &None, None) == pixels, "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn invert_twice_is_original()
+    {
+        let pixels = image();
+        let params = AdjustParams::default();
+        let inverted = adjust_pixels(&pixels, Adjustment::Invert, &params, &None, None);
+        assert!(inverted != pixels);
+        assert!(adjust_pixels(&inverted, Adjustment::Invert, &params, &None, None) == pixels);
+    }
+
+    #[test]
+    fn posterize_gives_levels()
+    {
+        for levels in [2, 3, 4, 8]
+        {
+            let params = AdjustParams { posterize: levels, ..Default::default() };
+            let ramp = vec![(0..=255).map(|v| (v, 255 - v, v / 2, 255)).collect()];
+            let result = adjust_pixels(&ramp, Adjustment::Posterize, &params, &None, None);
+            let mut values: Vec<u8> = result.iter().flatten().flat_map(|p| [p.0, p.1, p.2]).collect();
+            values.sort();
+            values.dedup();
+            assert_eq!(values.len(), levels as usize);
+            assert_eq!((values[0], values[values.len() - 1]), (0, 255));
+        }
+    }
+
+    #[test]
+    fn outside_selection_and_transparent_stay()
+    {
+        let mut pixels = image();
+        pixels[0][0] = (10, 20, 30, 0);
+        pixels[0][1] = (10, 20, 30, 0);
+        let width = pixels[0].len();
+        let mut selection = Selection::new(width, pixels.len());
+        for x in 0..width
+        {
+            selection.mask[x] = true;
+        }
+        let selection = Some(selection);
+
+        let result = adjust_pixels(&pixels, Adjustment::Invert, &AdjustParams::default(), &selection, None);
+        assert_eq!(result[0][0], (10, 20, 30, 0));
+        assert_eq!(result[0][1], (10, 20, 30, 0));
+        assert_eq!(result[0][2], (255 - pixels[0][2].0, 255 - pixels[0][2].1, 255 - pixels[0][2].2, pixels[0][2].3));
+        assert!(result[1..] == pixels[1..]);
+    }
+
+    #[test]
+    fn snapping_gives_palette_colors()
+    {
+        let colors = [(0, 0, 0, 255), (255, 255, 255, 255), (200, 40, 40, 255), (30, 90, 220, 255)];
+        let palette = palette(&colors);
+        let params = AdjustParams { hue: 40.0, ..Default::default() };
+        let result = adjust_pixels(&image(), Adjustment::HueSaturation, &params, &None, Some(&palette));
+        for pixel in result.iter().flatten()
+        {
+            //Alpha is kept from the pixel, only the colour comes from the palette
+            assert!(colors.iter().any(|c| (c.0, c.1, c.2) == (pixel.0, pixel.1, pixel.2)), "{:?}", pixel);
+        }
+    }
+
+    #[test]
+    fn curve_interpolates()
+    {
+        let curve = [(0.0, 0.0), (0.5, 0.8), (1.0, 1.0)];
+        assert_eq!(curve_value(&curve, 0.0), 0.0);
+        assert!((curve_value(&curve, 0.25) - 0.4).abs() < 1e-6);
+        assert!((curve_value(&curve, 0.75) - 0.9).abs() < 1e-6);
+        assert_eq!(curve_value(&curve, 1.0), 1.0);
+        assert_eq!(curve_value(&curve, 2.0), 1.0);
+        assert_eq!(curve_value(&[], 0.3), 0.3);
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+
+use crate::color::Color;
+use crate::document::Document;
+use crate::palette::swatch;
+use crate::selection::Selection;
+
+pub struct AnalysisSettings
+{
+    pub open: bool,
+    pub color_limit: usize,
+    pub count_transparent: bool,
+    stats: Option<((u64, u64, bool), ColorStats)>,//Counted again when the document, its canvas version or the setting changes
+}
+
+impl Default for AnalysisSettings
+{
+    fn default() -> Self
+    {
+        AnalysisSettings
+        {
+            open: false,
+            color_limit: 16,
+            count_transparent: false,
+            stats: None,
+        }
+    }
+}
+
+pub struct ColorStats
+{
+    pub colors: Vec<((u8, u8, u8, u8), usize)>,//Sorted by count, most used first
+    pub total: usize,
+    pub histogram: [[usize; 256]; 4],//One per channel (r, g, b, a)
+}
+
+pub fn color_stats(pixels: &[Vec<(u8, u8, u8, u8)>], count_transparent: bool) -> ColorStats
+{
+    let mut counts: HashMap<(u8, u8, u8, u8), usize> = HashMap::new();
+    let mut histogram = [[0; 256]; 4];
+    let mut total = 0;
+
+    for row in pixels
+    {
+        for pixel in row
+        {
+            if pixel.3 == 0 && !count_transparent
+            {
+                continue;
+            }
+
+            *counts.entry(*pixel).or_insert(0) += 1;
+            histogram[0][pixel.0 as usize] += 1;
+            histogram[1][pixel.1 as usize] += 1;
+            histogram[2][pixel.2 as usize] += 1;
+            histogram[3][pixel.3 as usize] += 1;
+            total += 1;
+        }
+    }
+
+    let mut colors: Vec<((u8, u8, u8, u8), usize)> = counts.into_iter().collect();
+    colors.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    ColorStats
+    {
+        colors,
+        total,
+        histogram
+    }
+}
+
+pub fn analysis_window(egui_ctx: &egui::Context, doc: &mut Document, settings: &mut AnalysisSettings)
+{
+    if !settings.open
+    {
+        settings.stats = None;
+        return;
+    }
+
+    let key = (doc.id, doc.canvas.version(), settings.count_transparent);
+    if settings.stats.as_ref().map(|(k, _)| *k != key).unwrap_or(true)
+    {
+        settings.stats = Some((key, color_stats(&doc.pixels, settings.count_transparent)));
+    }
+    let stats = match settings.stats.take()
+    {
+        Some((_, stats)) => stats,
+        None => return,
+    };
+
+    let mut open = settings.open;
+    egui::Window::new("Analysis").open(&mut open).show(egui_ctx, |ui|
+    {
+        ui.add(egui::Slider::new(&mut settings.color_limit, 1..=256).text("Color Limit"));
+        ui.checkbox(&mut settings.count_transparent, "Count transparent pixels");
+        ui.label(format!("{} colors, {} pixels", stats.colors.len(), stats.total));
+        if stats.colors.len() > settings.color_limit
+        {
+            ui.colored_label(egui::Color32::from_rgb(255, 90, 90), format!("Warning: {} colors over the limit", stats.colors.len() - settings.color_limit));
+        }
+        ui.separator();
+
+        ui.label("Histogram");
+        let channels = [egui::Color32::RED, egui::Color32::GREEN, egui::Color32::from_rgb(80, 120, 255), egui::Color32::GRAY];
+        for (channel, bar_color) in stats.histogram.iter().zip(channels)
+        {
+            histogram(ui, channel, bar_color);
+        }
+        ui.separator();
+
+        ui.label("Click a color to select its pixels");
+        //Only the visible rows are laid out, images can have many thousands of colours
+        let row_height = ui.spacing().interact_size.y.max(16.0);
+        egui::ScrollArea::vertical().max_height(250.0).show_rows(ui, row_height, stats.colors.len(), |ui, rows|
+        {
+            for (color, count) in stats.colors[rows].iter()
+            {
+                ui.horizontal(|ui|
+                {
+                    let response = swatch(ui, Color::from_u8(*color), 16.0);
+                    let text = format!("#{:02X}{:02X}{:02X}{:02X}  {}  ({:.1}%)", color.0, color.1, color.2, color.3, count, *count as f32 / stats.total as f32 * 100.0);
+                    if response.clicked() || ui.selectable_label(false, text).clicked()
+                    {
+                        doc.selection = Some(Selection::from_color(&doc.pixels, *color));
+                    }
+                });
+            }
+        });
+    });
+    settings.stats = Some((key, stats));
+    settings.open = open;
+}
+
+fn histogram(ui: &mut egui::Ui, bins: &[usize; 256], bar_color: egui::Color32)
+{
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(256.0, 40.0), egui::Sense::hover());
+    ui.painter().rect_filled(rect, 0.0, egui::Color32::from_gray(30));
+
+    let max = *bins.iter().max().unwrap_or(&0);
+    if max == 0
+    {
+        return;
+    }
+
+    for (i, count) in bins.iter().enumerate()
+    {
+        if *count == 0
+        {
+            continue;
+        }
+
+        let h = (*count as f32 / max as f32 * rect.height()).max(1.0);
+        let x = rect.left() + i as f32;
+        ui.painter().line_segment([egui::pos2(x, rect.bottom()), egui::pos2(x, rect.bottom() - h)], egui::Stroke::new(1.0, bar_color));
+    }
+}
@@ -0,0 +1,112 @@
+use macroquad::prelude::*;
+
+//What is drawn below the canvas, so transparent pixels can be told apart
+pub struct Backdrop
+{
+    pub checkerboard: bool,//Solid color when false
+    pub color_a: [f32; 4],
+    pub color_b: [f32; 4],
+    pub cell_size: f32,//In screen pixels
+    pub solid: [f32; 4],
+}
+
+impl Default for Backdrop
+{
+    fn default() -> Self
+    {
+        Backdrop
+        {
+            checkerboard: true,
+            color_a: [0.8, 0.8, 0.8, 1.0],
+            color_b: [0.55, 0.55, 0.55, 1.0],
+            cell_size: 8.0,
+            solid: [1.0, 1.0, 1.0, 1.0],
+        }
+    }
+}
+
+pub fn backdrop_ui(ui: &mut egui::Ui, backdrop: &mut Backdrop)
+{
+    ui.horizontal(|ui|
+    {
+        ui.radio_value(&mut backdrop.checkerboard, true, "Checkerboard");
+        ui.radio_value(&mut backdrop.checkerboard, false, "Solid");
+    });
+    if backdrop.checkerboard
+    {
+        ui.horizontal(|ui|
+        {
+            ui.color_edit_button_rgba_unmultiplied(&mut backdrop.color_a);
+            ui.color_edit_button_rgba_unmultiplied(&mut backdrop.color_b);
+        });
+        ui.add(egui::Slider::new(&mut backdrop.cell_size, 2.0..=64.0).text("Cell Size"));
+    }
+    else
+    {
+        ui.color_edit_button_rgba_unmultiplied(&mut backdrop.solid);
+    }
+}
+
+//The canvas texture is drawn over this with normal alpha blending
+pub fn draw_backdrop(material: Material, backdrop: &Backdrop, rect: Rect)
+{
+    if !backdrop.checkerboard
+    {
+        let c = backdrop.solid;
+        draw_rectangle(rect.x, rect.y, rect.w, rect.h, Color::new(c[0], c[1], c[2], c[3]));
+        return;
+    }
+
+    let (a, b) = (backdrop.color_a, backdrop.color_b);
+    gl_use_material(material);
+    material.set_uniform("size", vec2(rect.w, rect.h));
+    material.set_uniform("cell", backdrop.cell_size);
+    material.set_uniform("color_a", vec4(a[0], a[1], a[2], a[3]));
+    material.set_uniform("color_b", vec4(b[0], b[1], b[2], b[3]));
+    draw_rectangle(rect.x, rect.y, rect.w, rect.h, WHITE);
+    gl_use_default_material();
+}
+
+const CHECKER_VERTEX: &str = r#"#version 100
+attribute vec3 position;
+attribute vec2 texcoord;
+
+varying highp vec2 uv;
+
+uniform mat4 Model;
+uniform mat4 Projection;
+
+void main() {
+    gl_Position = Projection * Model * vec4(position, 1);
+    uv = texcoord;
+}"#;
+
+//Cells are counted from the top left corner of the canvas, so they move along when panning
+const CHECKER_FRAGMENT: &str = r#"#version 100
+precision highp float;
+
+varying highp vec2 uv;
+
+uniform vec2 size;
+uniform float cell;
+uniform vec4 color_a;
+uniform vec4 color_b;
+
+void main() {
+    vec2 c = floor(uv * size / cell);
+    gl_FragColor = mod(c.x + c.y, 2.0) < 1.0 ? color_a : color_b;
+}"#;
+
+pub fn checker_material() -> Material
+{
+    load_material(CHECKER_VERTEX, CHECKER_FRAGMENT, MaterialParams
+    {
+        uniforms: vec![
+            ("size".to_string(), UniformType::Float2),
+            ("cell".to_string(), UniformType::Float1),
+            ("color_a".to_string(), UniformType::Float4),
+            ("color_b".to_string(), UniformType::Float4),
+        ],
+        ..Default::default()
+    }).unwrap()
+}
@@ -0,0 +1,243 @@
+use macroquad::prelude::*;
+use macroquad::texture::Image as TextureImage;
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::palette::CycleMap;
+use crate::selection::Selection;
+use crate::view_filter::ViewFilter;
+
+//Shared by all canvases, so a version never shows up in two documents
+static NEXT_VERSION: AtomicU64 = AtomicU64::new(0);
+
+//GPU copy of the document, drawn as one texture instead of one rectangle per pixel
+//Edits only mark the changed area, which gets uploaded on the next frame
+pub struct Canvas
+{
+    pub width: usize,
+    pub height: usize,
+    texture: Texture2D,
+    filtered: Texture2D,
+    selection: Texture2D,
+    dirty: Option<(usize, usize, usize, usize)>,//x0, y0, x1, y1 (exclusive)
+    filter: ViewFilter,
+    cycle_map: CycleMap,
+    selection_mask: Vec<bool>,
+    version: u64,//Changes every time the texture changes, unique across canvases
+}
+
+impl Canvas
+{
+    pub fn new(width: usize, height: usize) -> Canvas
+    {
+        let empty = vec![0; width * height * 4];
+        let texture = Texture2D::from_rgba8(width as u16, height as u16, &empty);
+        let filtered = Texture2D::from_rgba8(width as u16, height as u16, &empty);
+        let selection = Texture2D::from_rgba8(width as u16, height as u16, &empty);
+        texture.set_filter(FilterMode::Nearest);
+        filtered.set_filter(FilterMode::Nearest);
+        selection.set_filter(FilterMode::Nearest);
+
+        Canvas
+        {
+            width,
+            height,
+            texture,
+            filtered,
+            selection,
+            dirty: Some((0, 0, width, height)),
+            filter: ViewFilter::None,
+            cycle_map: CycleMap::new(),
+            selection_mask: Vec::new(),
+            version: NEXT_VERSION.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    //Rectangle in pixels, parts outside of the canvas are ignored
+    pub fn mark_dirty(&mut self, x: i32, y: i32, w: i32, h: i32)
+    {
+        self.dirty = merge_dirty(self.dirty, (self.width, self.height), (x, y, w, h));
+    }
+
+    pub fn mark_all_dirty(&mut self)
+    {
+        self.dirty = Some((0, 0, self.width, self.height));
+    }
+
+    pub fn upload(&mut self, pixels: &[Vec<(u8, u8, u8, u8)>], filter: ViewFilter, cycle_map: &CycleMap)
+    {
+        if filter != self.filter || *cycle_map != self.cycle_map
+        {
+            self.filter = filter;
+            self.cycle_map = cycle_map.clone();
+            self.mark_all_dirty();
+        }
+
+        if let Some(rect) = self.dirty.take()
+        {
+            self.version = NEXT_VERSION.fetch_add(1, Ordering::Relaxed);
+            let (w, h) = ((rect.2 - rect.0) as u16, (rect.3 - rect.1) as u16);
+
+            let bytes = region_bytes(pixels, rect, ViewFilter::None, cycle_map);
+            self.texture.update_part(&TextureImage { bytes, width: w, height: h }, rect.0 as i32, rect.1 as i32, w as i32, h as i32);
+
+            if filter != ViewFilter::None
+            {
+                let bytes = region_bytes(pixels, rect, filter, cycle_map);
+                self.filtered.update_part(&TextureImage { bytes, width: w, height: h }, rect.0 as i32, rect.1 as i32, w as i32, h as i32);
+            }
+        }
+    }
+
+    pub fn version(&self) -> u64
+    {
+        self.version
+    }
+
+    pub fn upload_selection(&mut self, selection: &Option<Selection>)
+    {
+        let empty = Vec::new();
+        let mask = match selection
+        {
+            Some(s) => &s.mask,
+            None => &empty,
+        };
+        if *mask == self.selection_mask
+        {
+            return;
+        }
+
+        let mut bytes = vec![0; self.width * self.height * 4];
+        for (i, selected) in mask.iter().enumerate()
+        {
+            if *selected
+            {
+                bytes[i * 4..i * 4 + 4].copy_from_slice(&[77, 153, 255, 90]);
+            }
+        }
+        self.selection.update(&TextureImage { bytes, width: self.width as u16, height: self.height as u16 });
+        self.selection_mask = mask.clone();
+    }
+
+    pub fn draw(&self, x: f32, y: f32, w: f32, h: f32, filtered: bool)
+    {
+        let texture = if filtered && self.filter != ViewFilter::None { self.filtered } else { self.texture };
+        draw_texture_ex(texture, x, y, WHITE, DrawTextureParams { dest_size: Some(vec2(w, h)), ..Default::default() });
+    }
+
+    pub fn draw_selection(&self, x: f32, y: f32, w: f32, h: f32)
+    {
+        if !self.selection_mask.is_empty()
+        {
+            draw_texture_ex(self.selection, x, y, WHITE, DrawTextureParams { dest_size: Some(vec2(w, h)), ..Default::default() });
+        }
+    }
+}
+
+//Grows the dirty area (x0, y0, x1, y1) by the rectangle (x, y, w, h) clamped to the canvas
+fn merge_dirty(dirty: Option<(usize, usize, usize, usize)>, size: (usize, usize), rect: (i32, i32, i32, i32)) -> Option<(usize, usize, usize, usize)>
+{
+    let (x, y, w, h) = rect;
+    let x0 = x.clamp(0, size.0 as i32) as usize;
+    let y0 = y.clamp(0, size.1 as i32) as usize;
+    let x1 = x.saturating_add(w).clamp(0, size.0 as i32) as usize;
+    let y1 = y.saturating_add(h).clamp(0, size.1 as i32) as usize;
+    if x0 >= x1 || y0 >= y1
+    {
+        return dirty;
+    }
+
+    match dirty
+    {
+        Some(d) => Some((d.0.min(x0), d.1.min(y0), d.2.max(x1), d.3.max(y1))),
+        None => Some((x0, y0, x1, y1)),
+    }
+}
+
+//RGBA bytes of one rectangle of the document, the way it should be displayed
+pub fn region_bytes(pixels: &[Vec<(u8, u8, u8, u8)>], rect: (usize, usize, usize, usize), filter: ViewFilter, cycle_map: &CycleMap) -> Vec<u8>
+{
+    let mut bytes = Vec::with_capacity((rect.2 - rect.0) * (rect.3 - rect.1) * 4);
+
+    for row in pixels[rect.1..rect.3].iter()
+    {
+        for p in row[rect.0..rect.2].iter()
+        {
+            let c = filter.apply(*cycle_map.get(p).unwrap_or(p));
+            bytes.extend([c.0, c.1, c.2, c.3]);
+        }
+    }
+
+    bytes
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn dirty_rects_merge()
+    {
+        let size = (100, 50);
+        let dirty = merge_dirty(None, size, (10, 5, 4, 3));
+        assert_eq!(dirty, Some((10, 5, 14, 8)));
+        let dirty = merge_dirty(dirty, size, (20, 1, 2, 2));
+        assert_eq!(dirty, Some((10, 1, 22, 8)));
+        //Inside of the current area, nothing changes
+        assert_eq!(merge_dirty(dirty, size, (11, 2, 1, 1)), dirty);
+    }
+
+    #[test]
+    fn dirty_rects_clamp()
+    {
+        let size = (100, 50);
+        assert_eq!(merge_dirty(None, size, (-5, -5, 10, 10)), Some((0, 0, 5, 5)));
+        assert_eq!(merge_dirty(None, size, (95, 45, 10, 10)), Some((95, 45, 100, 50)));
+        assert_eq!(merge_dirty(None, size, (-10, -10, 1000, 1000)), Some((0, 0, 100, 50)));
+        assert_eq!(merge_dirty(None, size, (i32::MAX - 1, 0, i32::MAX, 10)), None);
+    }
+
+    #[test]
+    fn empty_or_outside_rects_are_ignored()
+    {
+        let size = (100, 50);
+        assert_eq!(merge_dirty(None, size, (10, 10, 0, 5)), None);
+        assert_eq!(merge_dirty(None, size, (10, 10, 5, -3)), None);
+        assert_eq!(merge_dirty(None, size, (-20, 10, 10, 5)), None);
+        assert_eq!(merge_dirty(None, size, (100, 10, 10, 5)), None);
+        let dirty = Some((1, 2, 3, 4));
+        assert_eq!(merge_dirty(dirty, size, (200, 200, 5, 5)), dirty);
+    }
+
+    #[test]
+    fn region_bytes_cut_out_the_rect()
+    {
+        let pixels: Vec<Vec<(u8, u8, u8, u8)>> = (0..4).map(|y| (0..4).map(|x| (x, y, 0, 255)).collect()).collect();
+        let bytes = region_bytes(&pixels, (1, 2, 3, 4), ViewFilter::None, &CycleMap::new());
+        assert_eq!(bytes, vec![1, 2, 0, 255, 2, 2, 0, 255, 1, 3, 0, 255, 2, 3, 0, 255]);
+    }
+
+    //Run with cargo test --release -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn region_bytes_timing()
+    {
+        let pixels: Vec<Vec<(u8, u8, u8, u8)>> = (0..512).map(|y| (0..512).map(|x| ((x % 256) as u8, (y % 256) as u8, 0, 255)).collect()).collect();
+        let cycle_map = CycleMap::new();
+        let runs = 100;
+
+        for (name, rect) in [("full", (0, 0, 512, 512)), ("partial", (200, 200, 232, 232))]
+        {
+            for filter in [ViewFilter::None, ViewFilter::Deuteranopia]
+            {
+                let start = std::time::Instant::now();
+                for _ in 0..runs
+                {
+                    std::hint::black_box(region_bytes(&pixels, rect, filter, &cycle_map));
+                }
+                println!("{} update, {}: {:?} per frame", name, filter.name(), start.elapsed() / runs);
+            }
+        }
+    }
+}
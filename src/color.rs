@@ -0,0 +1,413 @@
+//Straight (unmultiplied) sRGB colour, every channel in 0.0..=1.0
+//Hue is in degrees (0.0..360.0), alpha is passed through untouched by the conversions
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Color
+{
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Color
+{
+    pub const TRANSPARENT: Color = Color::new(0.0, 0.0, 0.0, 0.0);
+
+    pub const fn new(r: f32, g: f32, b: f32, a: f32) -> Color
+    {
+        Color
+        {
+            r,
+            g,
+            b,
+            a
+        }
+    }
+
+    pub fn from_array(rgba: [f32; 4]) -> Color
+    {
+        Color::new(rgba[0], rgba[1], rgba[2], rgba[3])
+    }
+
+    pub fn to_array(self) -> [f32; 4]
+    {
+        [self.r, self.g, self.b, self.a]
+    }
+
+    pub fn from_u8(u: (u8, u8, u8, u8)) -> Color
+    {
+        Color::new(u.0 as f32 / 255.0, u.1 as f32 / 255.0, u.2 as f32 / 255.0, u.3 as f32 / 255.0)
+    }
+
+    //Rounds to the nearest value, so from_u8 -> to_u8 always gives back the same bytes
+    pub fn to_u8(self) -> (u8, u8, u8, u8)
+    {
+        (channel_to_u8(self.r), channel_to_u8(self.g), channel_to_u8(self.b), channel_to_u8(self.a))
+    }
+
+    pub fn to_egui(self) -> egui::Color32
+    {
+        let (r, g, b, a) = self.to_u8();
+        egui::Color32::from_rgba_unmultiplied(r, g, b, a)
+    }
+
+    pub fn from_linear(rgba: [f32; 4]) -> Color
+    {
+        Color::new(linear_to_srgb(rgba[0]), linear_to_srgb(rgba[1]), linear_to_srgb(rgba[2]), rgba[3])
+    }
+
+    pub fn to_linear(self) -> [f32; 4]
+    {
+        [srgb_to_linear(self.r), srgb_to_linear(self.g), srgb_to_linear(self.b), self.a]
+    }
+
+    pub fn from_hsv(hsva: [f32; 4]) -> Color
+    {
+        let c = hsva[2] * hsva[1];
+        let m = hsva[2] - c;
+
+        from_chroma(hsva[0], c, m, hsva[3])
+    }
+
+    pub fn to_hsv(self) -> [f32; 4]
+    {
+        let (max, delta) = self.max_delta();
+        let s = if max > 0.0 { delta / max } else { 0.0 };
+
+        [self.hue(max, delta), s, max, self.a]
+    }
+
+    pub fn from_hsl(hsla: [f32; 4]) -> Color
+    {
+        let c = (1.0 - (2.0 * hsla[2] - 1.0).abs()) * hsla[1];
+        let m = hsla[2] - c / 2.0;
+
+        from_chroma(hsla[0], c, m, hsla[3])
+    }
+
+    pub fn to_hsl(self) -> [f32; 4]
+    {
+        let (max, delta) = self.max_delta();
+        let l = max - delta / 2.0;
+        let s = if delta > 0.0 { delta / (1.0 - (2.0 * l - 1.0).abs()) } else { 0.0 };
+
+        [self.hue(max, delta), s.clamp(0.0, 1.0), l, self.a]
+    }
+
+    //Colours outside of the sRGB gamut get clamped
+    pub fn from_oklab(laba: [f32; 4]) -> Color
+    {
+        let rgb = oklab_to_linear([laba[0], laba[1], laba[2]]);
+        Color::from_linear([rgb[0], rgb[1], rgb[2], laba[3]]).clamped()
+    }
+
+    pub fn to_oklab(self) -> [f32; 4]
+    {
+        let linear = self.to_linear();
+        let lab = linear_to_oklab([linear[0], linear[1], linear[2]]);
+        [lab[0], lab[1], lab[2], self.a]
+    }
+
+    //OKLCH: l in 0.0..=1.0, c roughly 0.0..=0.37, h in degrees
+    pub fn from_oklch(lcha: [f32; 4]) -> Color
+    {
+        let h = lcha[2].to_radians();
+        Color::from_oklab([lcha[0], lcha[1] * h.cos(), lcha[1] * h.sin(), lcha[3]])
+    }
+
+    pub fn to_oklch(self) -> [f32; 4]
+    {
+        let lab = self.to_oklab();
+
+        let c = (lab[1] * lab[1] + lab[2] * lab[2]).sqrt();
+        let h = if c > 1e-6 { lab[2].atan2(lab[1]).to_degrees().rem_euclid(360.0) } else { 0.0 };
+
+        [lab[0], c, h, self.a]
+    }
+
+    pub fn clamped(self) -> Color
+    {
+        Color::new(self.r.clamp(0.0, 1.0), self.g.clamp(0.0, 1.0), self.b.clamp(0.0, 1.0), self.a.clamp(0.0, 1.0))
+    }
+
+    pub fn premultiplied(self) -> [f32; 4]
+    {
+        [self.r * self.a, self.g * self.a, self.b * self.a, self.a]
+    }
+
+    pub fn from_premultiplied(rgba: [f32; 4]) -> Color
+    {
+        if rgba[3] <= 0.0
+        {
+            return Color::TRANSPARENT;
+        }
+
+        Color::new(rgba[0] / rgba[3], rgba[1] / rgba[3], rgba[2] / rgba[3], rgba[3])
+    }
+
+    //Porter-Duff "source over destination"
+    pub fn over(self, below: Color) -> Color
+    {
+        let src = self.premultiplied();
+        let dst = below.premultiplied();
+        let inv = 1.0 - src[3];
+
+        Color::from_premultiplied([src[0] + dst[0] * inv, src[1] + dst[1] * inv, src[2] + dst[2] * inv, src[3] + dst[3] * inv])
+    }
+
+    //Perceptual distance (deltaE in OKLab), alpha counts as an extra axis
+    pub fn distance_oklab(self, other: Color) -> f32
+    {
+        let a = self.to_oklab();
+        let b = other.to_oklab();
+        let d = [a[0] - b[0], a[1] - b[1], a[2] - b[2], a[3] - b[3]];
+        (d[0] * d[0] + d[1] * d[1] + d[2] * d[2] + d[3] * d[3]).sqrt()
+    }
+
+    //Shared match test for the fill tool, magic wand and quantiser
+    //A tolerance of 0.0 only matches colours with identical bytes
+    pub fn matches(self, other: Color, tolerance: f32) -> bool
+    {
+        if tolerance <= 0.0
+        {
+            return self.to_u8() == other.to_u8();
+        }
+
+        //Fully transparent pixels count as the same colour no matter what rgb they store
+        if self.a == 0.0 && other.a == 0.0
+        {
+            return true;
+        }
+
+        self.distance_oklab(other) <= tolerance
+    }
+
+    fn max_delta(self) -> (f32, f32)
+    {
+        let max = self.r.max(self.g).max(self.b);
+        let min = self.r.min(self.g).min(self.b);
+        (max, max - min)
+    }
+
+    fn hue(self, max: f32, delta: f32) -> f32
+    {
+        if delta <= 0.0
+        {
+            return 0.0;
+        }
+
+        let h = if max == self.r
+        {
+            ((self.g - self.b) / delta).rem_euclid(6.0)
+        }
+        else if max == self.g
+        {
+            (self.b - self.r) / delta + 2.0
+        }
+        else
+        {
+            (self.r - self.g) / delta + 4.0
+        };
+
+        h * 60.0
+    }
+}
+
+fn channel_to_u8(c: f32) -> u8
+{
+    (c.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+fn from_chroma(h: f32, c: f32, m: f32, a: f32) -> Color
+{
+    let h = h.rem_euclid(360.0) / 60.0;
+    let x = c * (1.0 - (h.rem_euclid(2.0) - 1.0).abs());
+
+    let (r, g, b) = match h as u32
+    {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+
+    Color::new(r + m, g + m, b + m, a)
+}
+
+fn srgb_to_linear(c: f32) -> f32
+{
+    if c <= 0.04045
+    {
+        c / 12.92
+    }
+    else
+    {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(c: f32) -> f32
+{
+    if c <= 0.0031308
+    {
+        c * 12.92
+    }
+    else
+    {
+        1.055 * c.max(0.0).powf(1.0 / 2.4) - 0.055
+    }
+}
+
+//https://bottosson.github.io/posts/oklab/
+#[allow(clippy::excessive_precision)]
+fn linear_to_oklab(rgb: [f32; 3]) -> [f32; 3]
+{
+    let l = 0.4122214708 * rgb[0] + 0.5363325363 * rgb[1] + 0.0514459929 * rgb[2];
+    let m = 0.2119034982 * rgb[0] + 0.6806995451 * rgb[1] + 0.1073969566 * rgb[2];
+    let s = 0.0883024619 * rgb[0] + 0.2817188376 * rgb[1] + 0.6299787005 * rgb[2];
+
+    let l = l.cbrt();
+    let m = m.cbrt();
+    let s = s.cbrt();
+
+    [
+        0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+        1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+        0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+    ]
+}
+
+#[allow(clippy::excessive_precision)]
+fn oklab_to_linear(lab: [f32; 3]) -> [f32; 3]
+{
+    let l = lab[0] + 0.3963377774 * lab[1] + 0.2158037573 * lab[2];
+    let m = lab[0] - 0.1055613458 * lab[1] - 0.0638541728 * lab[2];
+    let s = lab[0] - 0.0894841775 * lab[1] - 1.2914855480 * lab[2];
+
+    let l = l * l * l;
+    let m = m * m * m;
+    let s = s * s * s;
+
+    [
+        4.0767416621 * l - 3.3077115913 * m + 0.2309699292 * s,
+        -1.2684380046 * l + 2.6097574011 * m - 0.3413193965 * s,
+        -0.0041960863 * l - 0.7034186147 * m + 1.7076147010 * s,
+    ]
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    const EPSILON: f32 = 1e-4;
+
+    //Every rgb value on a coarse grid, plus both ends of every channel
+    fn samples() -> Vec<Color>
+    {
+        let steps = [0u8, 1, 17, 64, 127, 128, 200, 254, 255];
+        let mut colors = Vec::new();
+        for r in steps
+        {
+            for g in steps
+            {
+                for b in steps
+                {
+                    colors.push(Color::from_u8((r, g, b, 255)));
+                }
+            }
+        }
+        colors
+    }
+
+    fn assert_close(a: Color, b: Color, tolerance: f32)
+    {
+        let d = [a.r - b.r, a.g - b.g, a.b - b.b, a.a - b.a];
+        assert!(d.iter().all(|d| d.abs() <= tolerance), "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn u8_round_trip()
+    {
+        for v in 0..=255u8
+        {
+            assert_eq!(Color::from_u8((v, v, v, v)).to_u8(), (v, v, v, v));
+            assert_eq!(Color::from_u8((v, 0, 255 - v, 128)).to_u8(), (v, 0, 255 - v, 128));
+        }
+    }
+
+    #[test]
+    fn to_u8_rounds_to_nearest()
+    {
+        assert_eq!(Color::new(0.999, 0.999, 0.999, 0.999).to_u8(), (255, 255, 255, 255));
+        assert_eq!(Color::new(0.001, 0.5, 1.5, -0.5).to_u8(), (0, 128, 255, 0));
+    }
+
+    #[test]
+    fn hsv_round_trip()
+    {
+        for c in samples()
+        {
+            assert_close(Color::from_hsv(c.to_hsv()), c, EPSILON);
+        }
+    }
+
+    #[test]
+    fn hsl_round_trip()
+    {
+        for c in samples()
+        {
+            assert_close(Color::from_hsl(c.to_hsl()), c, EPSILON);
+        }
+    }
+
+    #[test]
+    fn linear_round_trip()
+    {
+        for c in samples()
+        {
+            assert_close(Color::from_linear(c.to_linear()), c, EPSILON);
+        }
+    }
+
+    #[test]
+    fn oklab_round_trip()
+    {
+        for c in samples()
+        {
+            assert_close(Color::from_oklab(c.to_oklab()), c, 1e-3);
+            assert_close(Color::from_oklch(c.to_oklch()), c, 1e-3);
+            assert_eq!(Color::from_oklab(c.to_oklab()).to_u8(), c.to_u8());
+        }
+    }
+
+    #[test]
+    fn over_matches_porter_duff()
+    {
+        let red = Color::new(1.0, 0.0, 0.0, 1.0);
+        let blue = Color::new(0.0, 0.0, 1.0, 1.0);
+        let half_red = Color::new(1.0, 0.0, 0.0, 0.5);
+
+        //Opaque source covers everything, transparent source changes nothing
+        assert_close(red.over(blue), red, EPSILON);
+        assert_close(Color::TRANSPARENT.over(blue), blue, EPSILON);
+        assert_close(red.over(Color::TRANSPARENT), red, EPSILON);
+        assert_eq!(Color::TRANSPARENT.over(Color::TRANSPARENT), Color::TRANSPARENT);
+
+        assert_close(half_red.over(blue), Color::new(0.5, 0.0, 0.5, 1.0), EPSILON);
+        //Two halves: alpha 0.5 + 0.5 * 0.5, colour weighted by how much each one covers
+        assert_close(half_red.over(Color::new(0.0, 0.0, 1.0, 0.5)), Color::new(2.0 / 3.0, 0.0, 1.0 / 3.0, 0.75), EPSILON);
+    }
+
+    #[test]
+    fn matches_with_tolerance()
+    {
+        let c = Color::from_u8((10, 20, 30, 255));
+        assert!(c.matches(c, 0.0));
+        assert!(!c.matches(Color::from_u8((11, 20, 30, 255)), 0.0));
+        assert!(c.matches(Color::from_u8((11, 20, 30, 255)), 0.05));
+        assert!(Color::from_u8((255, 0, 0, 0)).matches(Color::from_u8((0, 255, 0, 0)), 0.01));
+    }
+}
@@ -0,0 +1,302 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use macroquad::prelude::*;
+
+use crate::canvas::Canvas;
+use crate::palette::Palette;
+use crate::reference::Reference;
+use crate::selection::Selection;
+use crate::spray::SprayTool;
+use crate::viewport::Viewport;
+use crate::Image;
+
+const HISTORY_LIMIT: usize = 64;
+
+type Pixels = Vec<Vec<(u8, u8, u8, u8)>>;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+//Tool selection and brush, each document remembers its own
+#[derive(Default)]
+pub struct ToolState
+{
+    pub color_picker: bool,
+    pub eraser: bool,
+    pub fill_tool: bool,
+    pub select_tool: bool,
+    pub select_start: Option<(i32, i32)>,
+    pub brush_size: i32,
+    pub spray: SprayTool,
+    pub text: bool,
+    pub stroke: bool,//Mouse is held down since the last undo step was saved
+}
+
+struct Snapshot
+{
+    width: i32,
+    height: i32,
+    pixels: Vec<Vec<(u8, u8, u8, u8)>>,
+}
+
+#[derive(Default)]
+pub struct History
+{
+    undo: Vec<Snapshot>,
+    redo: Vec<Snapshot>,
+}
+
+impl History
+{
+    pub fn can_undo(&self) -> bool
+    {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool
+    {
+        !self.redo.is_empty()
+    }
+}
+
+//One open image with everything that belongs to it
+pub struct Document
+{
+    pub id: u64,//Stays the same while tabs are opened and closed
+    pub name: String,
+    pub width: i32,
+    pub height: i32,
+    pub pixels: Vec<Vec<(u8, u8, u8, u8)>>,
+    pub preview: Option<Pixels>,//Shown instead of the pixels, never saved
+    pub palette: Palette,
+    pub references: Vec<Reference>,
+    pub selection: Option<Selection>,
+    pub viewport: Viewport,
+    pub tools: ToolState,
+    pub history: History,
+    pub canvas: Canvas,
+}
+
+impl Document
+{
+    pub fn new(name: &str, width: i32, height: i32, pixels: Vec<Vec<(u8, u8, u8, u8)>>) -> Document
+    {
+        Document
+        {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            name: name.to_string(),
+            width,
+            height,
+            pixels,
+            preview: None,
+            palette: Palette::default(),
+            references: Vec::new(),
+            selection: None,
+            viewport: Viewport::default(),
+            tools: ToolState::default(),
+            history: History::default(),
+            canvas: Canvas::new(width as usize, height as usize),
+        }
+    }
+
+    pub fn blank(name: &str, width: i32, height: i32) -> Document
+    {
+        Document::new(name, width, height, vec![vec![(0, 0, 0, 0); width as usize]; height as usize])
+    }
+
+    pub fn from_image(name: &str, image: Image) -> Document
+    {
+        let (width, height) = (image.width as i32, image.height as i32);
+        let mut document = Document::new(name, width, height, crate::one_to_two(image.pixels, height as usize, width as usize));
+        document.palette = image.palette;
+        document.references = image.references;
+        document
+    }
+
+    pub fn to_image(&self) -> Image
+    {
+        let mut image = Image::new(self.width as u16, self.height as u16, crate::two_to_one(self.pixels.to_vec()));
+        image.palette = self.palette.clone();
+        image.references = self.references.clone();
+        image
+    }
+
+    //Whole canvas in view, area is (x, y, w, h) on screen
+    pub fn fit(&mut self, area: Rect)
+    {
+        self.viewport.fit((self.width as f32, self.height as f32), (area.x, area.y, area.w, area.h));
+    }
+
+    fn snapshot(&self) -> Snapshot
+    {
+        Snapshot
+        {
+            width: self.width,
+            height: self.height,
+            pixels: self.pixels.clone(),
+        }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot)
+    {
+        if snapshot.width != self.width || snapshot.height != self.height
+        {
+            self.selection = None;
+        }
+        self.width = snapshot.width;
+        self.height = snapshot.height;
+        self.pixels = snapshot.pixels;
+        self.canvas.mark_all_dirty();
+    }
+
+    //Saves the current pixels as an undo step, call before changing them
+    pub fn checkpoint(&mut self)
+    {
+        let snapshot = self.snapshot();
+        self.history.undo.push(snapshot);
+        if self.history.undo.len() > HISTORY_LIMIT
+        {
+            self.history.undo.remove(0);
+        }
+        self.history.redo.clear();
+    }
+
+    pub fn undo(&mut self)
+    {
+        if let Some(snapshot) = self.history.undo.pop()
+        {
+            let current = self.snapshot();
+            self.history.redo.push(current);
+            self.restore(snapshot);
+        }
+    }
+
+    pub fn redo(&mut self)
+    {
+        if let Some(snapshot) = self.history.redo.pop()
+        {
+            let current = self.snapshot();
+            self.history.undo.push(current);
+            self.restore(snapshot);
+        }
+    }
+}
+
+//Second pane on the right half of the workspace, with its own camera
+#[derive(Default)]
+pub struct SplitView
+{
+    pub enabled: bool,
+    pub document: usize,
+    pub viewport: Viewport,
+}
+
+impl SplitView
+{
+    //Screen x where the right pane starts
+    pub fn split_x(&self, workspace: Rect) -> f32
+    {
+        if self.enabled { (workspace.x + workspace.w / 2.0).floor() } else { workspace.x + workspace.w }
+    }
+
+    //(area on screen, document index, is the right pane)
+    pub fn panes(&self, current: usize, workspace: Rect) -> Vec<(Rect, usize, bool)>
+    {
+        let x = self.split_x(workspace);
+        let mut panes = vec![(Rect::new(workspace.x, workspace.y, x - workspace.x, workspace.h), current, false)];
+        if self.enabled
+        {
+            panes.push((Rect::new(x, workspace.y, workspace.x + workspace.w - x, workspace.h), self.document, true));
+        }
+        panes
+    }
+}
+
+//What the canvas shows, a preview only while it still has the size of the pixels
+pub fn display_pixels<'a>(pixels: &'a [Vec<(u8, u8, u8, u8)>], preview: &'a Option<Pixels>) -> &'a [Vec<(u8, u8, u8, u8)>]
+{
+    match preview
+    {
+        Some(p) if p.len() == pixels.len() && p.iter().zip(pixels).all(|(a, b)| a.len() == b.len()) => p,
+        _ => pixels,
+    }
+}
+
+//Draws in screen coordinates, but only inside of rect (the gl viewport is in physical pixels)
+pub fn pane_camera(rect: Rect, dpi_scale: f32) -> Camera2D
+{
+    let mut camera = Camera2D::from_display_rect(rect);
+    let physical = |v: f32| (v * dpi_scale).round() as i32;
+    camera.viewport = Some((physical(rect.x), physical(screen_height() - rect.y - rect.h), physical(rect.w), physical(rect.h)));
+    camera
+}
+
+//set_default_camera keeps the last viewport, so it has to be reset by hand
+pub fn reset_camera()
+{
+    let mut camera = Camera2D::from_display_rect(Rect::new(0.0, 0.0, screen_width(), screen_height()));
+    camera.viewport = None;
+    set_camera(&camera);
+    set_default_camera();
+}
+
+pub fn tab_bar(egui_ctx: &egui::Context, documents: &mut Vec<Document>, current: &mut usize, split: &mut SplitView, workspace: Rect)
+{
+    if documents.is_empty()
+    {
+        return;
+    }
+
+    egui::TopBottomPanel::bottom("tabs").show(egui_ctx, |ui|
+    {
+        ui.horizontal(|ui|
+        {
+            let name = |i: usize| if documents[i].name.is_empty() { format!("Untitled {}", i + 1) } else { documents[i].name.clone() };
+
+            let mut close = None;
+            for i in 0..documents.len()
+            {
+                if ui.selectable_label(i == *current, name(i)).clicked()
+                {
+                    *current = i;
+                }
+                if ui.small_button("x").clicked()
+                {
+                    close = Some(i);
+                }
+                ui.separator();
+            }
+
+            let before = split.enabled;
+            ui.checkbox(&mut split.enabled, "Split View");
+            if split.enabled && !before
+            {
+                split.document = *current;
+                split.viewport = documents[*current].viewport;
+                let x = split.split_x(workspace);
+                split.viewport.fit((documents[*current].width as f32, documents[*current].height as f32), (x, workspace.y, workspace.x + workspace.w - x, workspace.h));
+            }
+            if split.enabled
+            {
+                egui::ComboBox::from_id_source("split_document").selected_text(name(split.document)).show_ui(ui, |ui|
+                {
+                    for i in 0..documents.len()
+                    {
+                        ui.selectable_value(&mut split.document, i, name(i));
+                    }
+                });
+            }
+
+            if let Some(i) = close
+            {
+                documents.remove(i);
+                let fix = |index: &mut usize| if *index > i || *index == documents.len() { *index = index.saturating_sub(1) };
+                fix(current);
+                fix(&mut split.document);
+                if documents.is_empty()
+                {
+                    split.enabled = false;
+                }
+            }
+        });
+    });
+}
@@ -0,0 +1,250 @@
+use crate::color::Color;
+use crate::document::Document;
+use crate::selection::Selection;
+
+type Pixels = Vec<Vec<(u8, u8, u8, u8)>>;
+
+const SIDES: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+const CORNERS: [(i32, i32); 4] = [(1, 1), (-1, 1), (1, -1), (-1, -1)];
+
+pub struct EffectSettings
+{
+    pub outline_color: [f32; 4],
+    pub outside: bool,
+    pub diagonal: bool,//8-connected instead of 4-connected
+    pub shadow_color: [f32; 4],
+    pub shadow_offset: (i32, i32),
+    pub inner_color: [f32; 4],
+    pub inner_offset: (i32, i32),//Direction the light comes from
+}
+
+impl Default for EffectSettings
+{
+    fn default() -> Self
+    {
+        EffectSettings
+        {
+            outline_color: [0.0, 0.0, 0.0, 1.0],
+            outside: true,
+            diagonal: false,
+            shadow_color: [0.0, 0.0, 0.0, 0.5],
+            shadow_offset: (1, 1),
+            inner_color: [1.0, 1.0, 1.0, 0.5],
+            inner_offset: (-1, -1),
+        }
+    }
+}
+
+//The shape an effect works on: opaque pixels, only the selected ones when there is a selection
+fn shape<'a>(pixels: &'a Pixels, selection: &'a Option<Selection>) -> impl Fn(i32, i32) -> bool + 'a
+{
+    let selection = selection.as_ref();
+    move |x, y|
+    {
+        x >= 0 && y >= 0 && (y as usize) < pixels.len() && (x as usize) < pixels[y as usize].len()
+            && pixels[y as usize][x as usize].3 > 0
+            && selection.map(|s| s.contains(x, y)).unwrap_or(true)
+    }
+}
+
+//Blends the colour over every pixel the closure picks
+fn paint(pixels: &Pixels, color: Color, mut pick: impl FnMut(i32, i32) -> bool) -> Pixels
+{
+    let mut result = pixels.clone();
+
+    for (y, row) in result.iter_mut().enumerate()
+    {
+        for (x, pixel) in row.iter_mut().enumerate()
+        {
+            if pick(x as i32, y as i32)
+            {
+                *pixel = color.over(Color::from_u8(*pixel)).to_u8();
+            }
+        }
+    }
+
+    result
+}
+
+//Outside puts the line on empty pixels next to the shape, inside on the edge pixels of the shape
+pub fn outline(pixels: &Pixels, selection: &Option<Selection>, color: Color, outside: bool, diagonal: bool) -> Pixels
+{
+    let inside = shape(pixels, selection);
+    let neighbours: Vec<(i32, i32)> = if diagonal { SIDES.iter().chain(CORNERS.iter()).copied().collect() } else { SIDES.to_vec() };
+
+    paint(pixels, color, |x, y|
+    {
+        if outside
+        {
+            pixels[y as usize][x as usize].3 == 0 && neighbours.iter().any(|(dx, dy)| inside(x + dx, y + dy))
+        }
+        else
+        {
+            inside(x, y) && neighbours.iter().any(|(dx, dy)| !inside(x + dx, y + dy))
+        }
+    })
+}
+
+//Hard copy of the shape moved by the offset, only on empty pixels
+pub fn drop_shadow(pixels: &Pixels, selection: &Option<Selection>, color: Color, offset: (i32, i32)) -> Pixels
+{
+    let inside = shape(pixels, selection);
+    paint(pixels, color, |x, y| pixels[y as usize][x as usize].3 == 0 && inside(x - offset.0, y - offset.1))
+}
+
+//Edge pixels of the shape facing the offset, a light colour gives a highlight and a dark one a shadow
+pub fn inner_shade(pixels: &Pixels, selection: &Option<Selection>, color: Color, offset: (i32, i32)) -> Pixels
+{
+    let inside = shape(pixels, selection);
+    paint(pixels, color, |x, y| inside(x, y) && !inside(x + offset.0, y + offset.1))
+}
+
+impl Document
+{
+    fn apply_effect(&mut self, f: impl Fn(&Pixels, &Option<Selection>) -> Pixels)
+    {
+        self.checkpoint();
+        self.pixels = f(&self.pixels, &self.selection);
+        self.canvas.mark_all_dirty();
+    }
+}
+
+pub fn effects_ui(ui: &mut egui::Ui, settings: &mut EffectSettings, doc: &mut Document)
+{
+    ui.collapsing("Effects", |ui|
+    {
+        if doc.selection.is_some()
+        {
+            ui.label("Uses the selected pixels as the shape");
+        }
+
+        ui.horizontal(|ui|
+        {
+            if ui.button("Outline").clicked()
+            {
+                let (color, outside, diagonal) = (Color::from_array(settings.outline_color), settings.outside, settings.diagonal);
+                doc.apply_effect(|pixels, selection| outline(pixels, selection, color, outside, diagonal));
+            }
+            ui.color_edit_button_rgba_unmultiplied(&mut settings.outline_color);
+            ui.checkbox(&mut settings.outside, "Outside");
+            ui.checkbox(&mut settings.diagonal, "8-connected");
+        });
+
+        ui.horizontal(|ui|
+        {
+            if ui.button("Drop Shadow").clicked()
+            {
+                let (color, offset) = (Color::from_array(settings.shadow_color), settings.shadow_offset);
+                doc.apply_effect(|pixels, selection| drop_shadow(pixels, selection, color, offset));
+            }
+            ui.color_edit_button_rgba_unmultiplied(&mut settings.shadow_color);
+            offset_ui(ui, &mut settings.shadow_offset);
+        });
+
+        ui.horizontal(|ui|
+        {
+            if ui.button("Inner Shade").clicked()
+            {
+                let (color, offset) = (Color::from_array(settings.inner_color), settings.inner_offset);
+                doc.apply_effect(|pixels, selection| inner_shade(pixels, selection, color, offset));
+            }
+            ui.color_edit_button_rgba_unmultiplied(&mut settings.inner_color);
+            offset_ui(ui, &mut settings.inner_offset);
+        });
+    });
+}
+
+fn offset_ui(ui: &mut egui::Ui, offset: &mut (i32, i32))
+{
+    ui.add(egui::DragValue::new(&mut offset.0).clamp_range(-16..=16).prefix("x "));
+    ui.add(egui::DragValue::new(&mut offset.1).clamp_range(-16..=16).prefix("y "));
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    const SHAPE: (u8, u8, u8, u8) = (200, 50, 50, 255);
+    const LINE: Color = Color::new(0.0, 0.0, 1.0, 1.0);
+    const EMPTY: (u8, u8, u8, u8) = (0, 0, 0, 0);
+
+    //'#' is the shape, '.' is empty
+    fn grid(rows: &[&str]) -> Pixels
+    {
+        rows.iter().map(|row| row.chars().map(|c| if c == '#' { SHAPE } else { EMPTY }).collect()).collect()
+    }
+
+    //'o' marks pixels the effect painted
+    fn show(pixels: &Pixels) -> Vec<String>
+    {
+        pixels.iter().map(|row| row.iter().map(|p| match *p
+        {
+            SHAPE => '#',
+            EMPTY => '.',
+            p if p == LINE.to_u8() => 'o',
+            _ => '?',
+        }).collect()).collect()
+    }
+
+    const DOT: [&str; 5] = [".....", ".....", "..#..", ".....", "....."];
+
+    #[test]
+    fn outside_outline_4_and_8_connected()
+    {
+        let four = outline(&grid(&DOT), &None, LINE, true, false);
+        assert_eq!(show(&four), [".....", "..o..", ".o#o.", "..o..", "....."]);
+        let eight = outline(&grid(&DOT), &None, LINE, true, true);
+        assert_eq!(show(&eight), [".....", ".ooo.", ".o#o.", ".ooo.", "....."]);
+    }
+
+    #[test]
+    fn inside_outline_only_on_edge()
+    {
+        let block = [".....", ".###.", ".###.", ".###.", "....."];
+        let four = outline(&grid(&block), &None, LINE, false, false);
+        assert_eq!(show(&four), [".....", ".ooo.", ".o#o.", ".ooo.", "....."]);
+
+        //The centre only touches the notch diagonally
+        let notched = [".....", ".###.", ".###.", ".##..", "....."];
+        let four = outline(&grid(&notched), &None, LINE, false, false);
+        assert_eq!(show(&four), [".....", ".ooo.", ".o#o.", ".oo..", "....."]);
+        let eight = outline(&grid(&notched), &None, LINE, false, true);
+        assert_eq!(show(&eight), [".....", ".ooo.", ".ooo.", ".oo..", "....."]);
+    }
+
+    #[test]
+    fn shadow_is_offset()
+    {
+        let shadow = drop_shadow(&grid(&DOT), &None, LINE, (1, 2));
+        assert_eq!(show(&shadow), [".....", ".....", "..#..", ".....", "...o."]);
+
+        //Never on top of the shape itself
+        let bar = [".....", ".##..", ".....", ".....", "....."];
+        let shadow = drop_shadow(&grid(&bar), &None, LINE, (1, 0));
+        assert_eq!(show(&shadow), [".....", ".##o.", ".....", ".....", "....."]);
+    }
+
+    #[test]
+    fn inner_shade_faces_offset()
+    {
+        let block = [".....", ".###.", ".###.", ".###.", "....."];
+        let shade = inner_shade(&grid(&block), &None, LINE, (-1, -1));
+        assert_eq!(show(&shade), [".....", ".ooo.", ".o##.", ".o##.", "....."]);
+    }
+
+    #[test]
+    fn selection_limits_shape()
+    {
+        let two = [".....", ".#.#.", ".....", ".....", "....."];
+        let pixels = grid(&two);
+        let mut selection = Selection::new(5, 5);
+        selection.mask[5 + 1] = true;
+        let selection = Some(selection);
+
+        let lined = outline(&pixels, &selection, LINE, true, false);
+        assert_eq!(show(&lined), [".o...", "o#o#.", ".o...", ".....", "....."]);
+        let shadow = drop_shadow(&pixels, &selection, LINE, (0, 1));
+        assert_eq!(show(&shadow), [".....", ".#.#.", ".o...", ".....", "....."]);
+    }
+}
@@ -0,0 +1,353 @@
+use std::collections::HashMap;
+
+use image::RgbaImage;
+
+//One character, bits are row by row, y_offset is from the baseline to the top row (negative is above)
+#[derive(Clone, Debug, Default)]
+pub struct Glyph
+{
+    pub width: i32,
+    pub height: i32,
+    pub x_offset: i32,
+    pub y_offset: i32,
+    pub advance: i32,
+    pub bits: Vec<bool>,
+}
+
+impl Glyph
+{
+    pub fn get(&self, x: i32, y: i32) -> bool
+    {
+        x >= 0 && y >= 0 && x < self.width && y < self.height && self.bits[(y * self.width + x) as usize]
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct BitmapFont
+{
+    pub name: String,
+    pub ascent: i32,//Pixels above the baseline
+    pub descent: i32,//Pixels below the baseline
+    pub glyphs: HashMap<char, Glyph>,
+}
+
+//Larger boxes in a BDF file are treated as broken
+const MAX_GLYPH_SIZE: i32 = 1024;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Align
+{
+    Left,
+    Center,
+    Right,
+}
+
+//3x5 pixel font, every row is 3 bits with the left pixel in the highest bit
+const BUILTIN: [(char, [u8; 5]); 48] =
+[
+    ('A', [2, 5, 7, 5, 5]), ('B', [6, 5, 6, 5, 6]), ('C', [3, 4, 4, 4, 3]), ('D', [6, 5, 5, 5, 6]),
+    ('E', [7, 4, 6, 4, 7]), ('F', [7, 4, 6, 4, 4]), ('G', [3, 4, 5, 5, 3]), ('H', [5, 5, 7, 5, 5]),
+    ('I', [7, 2, 2, 2, 7]), ('J', [1, 1, 1, 5, 2]), ('K', [5, 5, 6, 5, 5]), ('L', [4, 4, 4, 4, 7]),
+    ('M', [5, 7, 7, 5, 5]), ('N', [6, 5, 5, 5, 5]), ('O', [2, 5, 5, 5, 2]), ('P', [6, 5, 6, 4, 4]),
+    ('Q', [2, 5, 5, 6, 3]), ('R', [6, 5, 6, 5, 5]), ('S', [3, 4, 2, 1, 6]), ('T', [7, 2, 2, 2, 2]),
+    ('U', [5, 5, 5, 5, 7]), ('V', [5, 5, 5, 5, 2]), ('W', [5, 5, 7, 7, 5]), ('X', [5, 5, 2, 5, 5]),
+    ('Y', [5, 5, 2, 2, 2]), ('Z', [7, 1, 2, 4, 7]),
+    ('0', [7, 5, 5, 5, 7]), ('1', [2, 6, 2, 2, 7]), ('2', [6, 1, 2, 4, 7]), ('3', [6, 1, 2, 1, 6]),
+    ('4', [5, 5, 7, 1, 1]), ('5', [7, 4, 6, 1, 6]), ('6', [3, 4, 6, 5, 2]), ('7', [7, 1, 2, 2, 2]),
+    ('8', [2, 5, 2, 5, 2]), ('9', [2, 5, 3, 1, 6]),
+    ('.', [0, 0, 0, 0, 2]), (',', [0, 0, 0, 2, 4]), ('!', [2, 2, 2, 0, 2]), ('?', [6, 1, 2, 0, 2]),
+    (':', [0, 2, 0, 2, 0]), ('-', [0, 0, 7, 0, 0]), ('+', [0, 2, 7, 2, 0]), ('/', [1, 1, 2, 4, 4]),
+    ('\'', [2, 2, 0, 0, 0]), ('(', [1, 2, 2, 2, 1]), (')', [4, 2, 2, 2, 4]), ('=', [0, 7, 0, 7, 0]),
+];
+
+impl BitmapFont
+{
+    pub fn builtin() -> BitmapFont
+    {
+        let mut glyphs: HashMap<char, Glyph> = BUILTIN.iter().map(|(c, rows)|
+        {
+            let bits = rows.iter().flat_map(|row| (0..3).map(move |x| row & (4 >> x) != 0)).collect();
+            (*c, Glyph { width: 3, height: 5, x_offset: 0, y_offset: -5, advance: 4, bits })
+        }).collect();
+        glyphs.insert(' ', Glyph { advance: 4, ..Default::default() });
+
+        BitmapFont
+        {
+            name: "Built-in 3x5".to_string(),
+            ascent: 5,
+            descent: 0,
+            glyphs,
+        }
+    }
+
+    //Only the parts needed for drawing are read: ascent, descent, encoding, advance, box and bitmap
+    pub fn from_bdf(name: &str, text: &str) -> Option<BitmapFont>
+    {
+        let mut font = BitmapFont { name: name.to_string(), ..Default::default() };
+        let mut bounding_box = None;
+        let mut lines = text.lines();
+
+        while let Some(line) = lines.next()
+        {
+            let mut words = line.split_whitespace();
+            let numbers = |words: std::str::SplitWhitespace| -> Vec<i32> { words.filter_map(|w| w.parse().ok()).collect() };
+            match words.next()
+            {
+                Some("FONT_ASCENT") => font.ascent = *numbers(words).first()?,
+                Some("FONT_DESCENT") => font.descent = *numbers(words).first()?,
+                Some("FONTBOUNDINGBOX") => bounding_box = Some(numbers(words)),
+                Some("STARTCHAR") =>
+                {
+                    let mut encoding = None;
+                    let mut glyph = Glyph::default();
+                    let mut broken = false;//Glyphs with an impossible box or bitmap are left out
+                    while let Some(line) = lines.next()
+                    {
+                        let mut words = line.split_whitespace();
+                        match words.next()
+                        {
+                            Some("ENCODING") => encoding = numbers(words).first().and_then(|e| char::from_u32(*e as u32)),
+                            Some("DWIDTH") => glyph.advance = *numbers(words).first()?,
+                            Some("BBX") =>
+                            {
+                                let n = numbers(words);
+                                let (w, h, x, y) = (*n.first()?, *n.get(1)?, *n.get(2)?, *n.get(3)?);
+                                let area = w.checked_mul(h).filter(|_| (0..=MAX_GLYPH_SIZE).contains(&w) && (0..=MAX_GLYPH_SIZE).contains(&h));
+                                match (area, y.checked_add(h).and_then(i32::checked_neg))
+                                {
+                                    (Some(area), Some(y_offset)) => glyph = Glyph { width: w, height: h, x_offset: x, y_offset, bits: vec![false; area as usize], ..glyph },
+                                    _ => broken = true,
+                                }
+                            }
+                            Some("BITMAP") =>
+                            {
+                                for y in 0..glyph.height
+                                {
+                                    let row = lines.next()?.trim();
+                                    let bytes: Option<Vec<u8>> = row.as_bytes().chunks_exact(2).map(|pair| std::str::from_utf8(pair).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok())).collect();
+                                    let bytes = bytes.unwrap_or_else(||
+                                    {
+                                        broken = true;
+                                        Vec::new()
+                                    });
+                                    for x in 0..glyph.width
+                                    {
+                                        let set = bytes.get(x as usize / 8).map(|b| b & (0x80 >> (x % 8)) != 0).unwrap_or(false);
+                                        glyph.bits[(y * glyph.width + x) as usize] = set;
+                                    }
+                                }
+                            }
+                            Some("ENDCHAR") => break,
+                            _ => {}
+                        }
+                    }
+                    if let (Some(c), false) = (encoding, broken)
+                    {
+                        font.glyphs.insert(c, glyph);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        //Older fonts only have the bounding box
+        if font.ascent == 0 && font.descent == 0
+        {
+            let b = bounding_box?;
+            font.descent = -*b.get(3)?;
+            font.ascent = *b.get(1)? - font.descent;
+        }
+
+        if font.glyphs.is_empty() { None } else { Some(font) }
+    }
+
+    //Glyphs in cells of the same size, left to right and top to bottom in the order of chars
+    //Any pixel that is not transparent is set, the advance is the used width plus one
+    pub fn from_sheet(name: &str, sheet: &RgbaImage, cell: (u32, u32), chars: &str) -> BitmapFont
+    {
+        let (cw, ch) = (cell.0.max(1), cell.1.max(1));
+        let columns = (sheet.width() / cw).max(1);
+        let mut glyphs = HashMap::new();
+
+        for (i, c) in chars.chars().enumerate()
+        {
+            let (cx, cy) = (i as u32 % columns * cw, i as u32 / columns * ch);
+            if cy + ch > sheet.height() || cx + cw > sheet.width()
+            {
+                break;
+            }
+
+            let bits: Vec<bool> = (0..ch).flat_map(|y| (0..cw).map(move |x| (x, y))).map(|(x, y)| sheet.get_pixel(cx + x, cy + y).0[3] > 0).collect();
+            let used = (0..cw as usize).filter(|x| (0..ch as usize).any(|y| bits[y * cw as usize + x])).max().map(|x| x as i32 + 1);
+            glyphs.insert(c, Glyph
+            {
+                width: cw as i32,
+                height: ch as i32,
+                x_offset: 0,
+                y_offset: -(ch as i32),
+                advance: used.unwrap_or(cw as i32 / 2) + 1,
+                bits,
+            });
+        }
+
+        BitmapFont
+        {
+            name: name.to_string(),
+            ascent: ch as i32,
+            descent: 0,
+            glyphs,
+        }
+    }
+
+    //Missing lowercase letters fall back to uppercase, anything else missing to '?'
+    pub fn glyph(&self, c: char) -> Option<&Glyph>
+    {
+        self.glyphs.get(&c).or_else(|| self.glyphs.get(&c.to_ascii_uppercase())).or_else(|| self.glyphs.get(&'?'))
+    }
+
+    pub fn line_width(&self, line: &str, spacing: i32) -> i32
+    {
+        let advances: Vec<i32> = line.chars().filter_map(|c| self.glyph(c)).map(|g| g.advance).collect();
+        advances.iter().sum::<i32>() + spacing * (advances.len() as i32 - 1).max(0)
+    }
+
+    pub fn line_height(&self, line_spacing: i32) -> i32
+    {
+        self.ascent + self.descent + line_spacing
+    }
+}
+
+//Set pixels of the text, every line is aligned inside of the widest one
+pub fn render_text(font: &BitmapFont, text: &str, align: Align, spacing: i32, line_spacing: i32) -> Vec<Vec<bool>>
+{
+    let lines: Vec<&str> = text.lines().collect();
+    let width = lines.iter().map(|l| font.line_width(l, spacing)).max().unwrap_or(0).max(0);
+    let line_height = font.line_height(line_spacing).max(1);
+    let height = (line_height * lines.len() as i32 - line_spacing).max(0);
+    let mut mask = vec![vec![false; width as usize]; height as usize];
+
+    for (i, line) in lines.iter().enumerate()
+    {
+        let free = width - font.line_width(line, spacing);
+        let mut pen = match align
+        {
+            Align::Left => 0,
+            Align::Center => free / 2,
+            Align::Right => free,
+        };
+        let baseline = i as i32 * line_height + font.ascent;
+
+        for glyph in line.chars().filter_map(|c| font.glyph(c))
+        {
+            for gy in 0..glyph.height
+            {
+                for gx in 0..glyph.width
+                {
+                    let (x, y) = (pen + glyph.x_offset + gx, baseline + glyph.y_offset + gy);
+                    if glyph.get(gx, gy) && x >= 0 && y >= 0 && x < width && y < height
+                    {
+                        mask[y as usize][x as usize] = true;
+                    }
+                }
+            }
+            pen += glyph.advance + spacing;
+        }
+    }
+
+    mask
+}
+
+//Glyph bitmaps are written as they are, so trimmed glyphs give the smallest file
+pub fn to_bdf(font: &BitmapFont) -> String
+{
+    let mut glyphs: Vec<(&char, &Glyph)> = font.glyphs.iter().collect();
+    glyphs.sort_by_key(|(c, _)| **c);
+
+    let size = (font.ascent + font.descent).max(1);
+    let (mut x0, mut y0, mut x1, mut y1) = (0, -font.descent, 0, font.ascent);
+    for (_, g) in glyphs.iter()
+    {
+        x0 = x0.min(g.x_offset);
+        x1 = x1.max(g.x_offset + g.width);
+        y0 = y0.min(-(g.y_offset + g.height));
+        y1 = y1.max(-g.y_offset);
+    }
+
+    let mut bdf = String::new();
+    bdf += "STARTFONT 2.1\n";
+    bdf += &format!("FONT {}\n", font.name.replace(' ', "_"));
+    bdf += &format!("SIZE {} 75 75\n", size);
+    bdf += &format!("FONTBOUNDINGBOX {} {} {} {}\n", x1 - x0, y1 - y0, x0, y0);
+    bdf += "STARTPROPERTIES 2\n";
+    bdf += &format!("FONT_ASCENT {}\nFONT_DESCENT {}\n", font.ascent, font.descent);
+    bdf += "ENDPROPERTIES\n";
+    bdf += &format!("CHARS {}\n", glyphs.len());
+
+    for (c, g) in glyphs
+    {
+        bdf += &format!("STARTCHAR U+{:04X}\nENCODING {}\n", *c as u32, *c as u32);
+        bdf += &format!("SWIDTH {} 0\nDWIDTH {} 0\n", g.advance * 1000 / size, g.advance);
+        bdf += &format!("BBX {} {} {} {}\nBITMAP\n", g.width, g.height, g.x_offset, -(g.y_offset + g.height));
+        for y in 0..g.height
+        {
+            //Rows are padded to whole bytes, the left pixel is the highest bit
+            let mut bytes = vec![0u8; ((g.width + 7) / 8) as usize];
+            for x in 0..g.width
+            {
+                if g.get(x, y)
+                {
+                    bytes[x as usize / 8] |= 0x80 >> (x % 8);
+                }
+            }
+            bdf += &bytes.iter().map(|b| format!("{:02X}", b)).collect::<String>();
+            bdf += "\n";
+        }
+        bdf += "ENDCHAR\n";
+    }
+
+    bdf += "ENDFONT\n";
+    bdf
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn bdf(glyphs: &str) -> String
+    {
+        format!("STARTFONT 2.1\nFONTBOUNDINGBOX 4 6 0 -1\nFONT_ASCENT 5\nFONT_DESCENT 1\nCHARS 2\n{}ENDFONT\n", glyphs)
+    }
+
+    const GOOD: &str = "STARTCHAR A\nENCODING 65\nDWIDTH 5 0\nBBX 4 2 0 0\nBITMAP\n90\nF0\nENDCHAR\n";
+
+    #[test]
+    fn reads_glyphs()
+    {
+        let font = BitmapFont::from_bdf("test", &bdf(GOOD)).unwrap();
+        assert_eq!((font.ascent, font.descent), (5, 1));
+        let glyph = &font.glyphs[&'A'];
+        assert_eq!((glyph.width, glyph.height, glyph.y_offset, glyph.advance), (4, 2, -2, 5));
+        assert_eq!(glyph.bits, vec![true, false, false, true, true, true, true, true]);
+    }
+
+    #[test]
+    fn non_ascii_bitmaps_are_rejected()
+    {
+        let text = bdf(&format!("{}STARTCHAR B\nENCODING 66\nDWIDTH 5 0\nBBX 4 2 0 0\nBITMAP\nä0\n9é\nENDCHAR\n", GOOD));
+        let font = BitmapFont::from_bdf("test", &text).unwrap();
+        assert!(font.glyphs.contains_key(&'A'));
+        assert!(!font.glyphs.contains_key(&'B'));
+    }
+
+    #[test]
+    fn impossible_boxes_are_rejected()
+    {
+        for bbx in ["BBX 65536 65536 0 0", "BBX -4 2 0 0", "BBX 4 2 0 2147483647", "BBX 2147483647 2 0 0"]
+        {
+            let text = bdf(&format!("{}STARTCHAR B\nENCODING 66\n{}\nBITMAP\nENDCHAR\n", GOOD, bbx));
+            let font = BitmapFont::from_bdf("test", &text).unwrap();
+            assert!(!font.glyphs.contains_key(&'B'), "{}", bbx);
+        }
+    }
+}
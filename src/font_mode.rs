@@ -0,0 +1,345 @@
+use crate::document::Document;
+use crate::font::{self, Align, BitmapFont, Glyph};
+use crate::grid::GridSettings;
+use crate::text::TextTool;
+
+const PREVIEW_COLOR: egui::Color32 = egui::Color32::WHITE;
+
+//Settings of one glyph, None means it is worked out from the pixels
+#[derive(Clone, Copy, Default)]
+pub struct GlyphMetrics
+{
+    pub advance: Option<i32>,
+    pub baseline: Option<i32>,//Rows from the top of the cell
+}
+
+//A glyph of the designed font together with where it is on the page
+pub struct PageGlyph
+{
+    pub c: char,
+    pub glyph: Glyph,
+    pub x: i32,
+    pub y: i32,
+}
+
+//Every cell of the tile grid is one glyph, in the order of chars
+pub struct FontDesign
+{
+    pub open: bool,
+    pub name: String,
+    pub chars: String,
+    pub baseline: i32,
+    pub spacing: i32,//Added to the automatic advance
+    pub metrics: Vec<GlyphMetrics>,
+    pub sample: String,
+    pub zoom: f32,
+    pub path: String,
+    preview: Option<(Vec<Vec<bool>>, egui::TextureHandle)>,
+}
+
+impl Default for FontDesign
+{
+    fn default() -> Self
+    {
+        FontDesign
+        {
+            open: false,
+            name: "Pixel Font".to_string(),
+            chars: (' '..='~').collect(),
+            baseline: 12,
+            spacing: 1,
+            metrics: Vec::new(),
+            sample: "The quick brown fox\njumps over the lazy dog 0123456789".to_string(),
+            zoom: 2.0,
+            path: String::new(),
+            preview: None,
+        }
+    }
+}
+
+impl FontDesign
+{
+    //Glyphs are trimmed to their set pixels, empty cells still get an advance
+    //A character listed twice only uses its first cell
+    pub fn glyphs(&self, doc: &Document, grid: &GridSettings) -> Vec<PageGlyph>
+    {
+        let (tw, th) = (grid.tile_size.0.max(1), grid.tile_size.1.max(1));
+        let (ox, oy) = grid.tile_offset;
+        let columns = ((doc.width - ox) / tw).max(1);
+        let mut glyphs: Vec<PageGlyph> = Vec::new();
+
+        for (i, c) in self.chars.chars().enumerate()
+        {
+            let (cx, cy) = (ox + i as i32 % columns * tw, oy + i as i32 / columns * th);
+            if cx < 0 || cy < 0 || cx + tw > doc.width || cy + th > doc.height
+            {
+                break;
+            }
+            if glyphs.iter().any(|g| g.c == c)
+            {
+                continue;
+            }
+
+            let set = |x: i32, y: i32| doc.pixels[(cy + y) as usize][(cx + x) as usize].3 > 0;
+            let mut bounds: Option<(i32, i32, i32, i32)> = None;
+            for y in 0..th
+            {
+                for x in (0..tw).filter(|x| set(*x, y))
+                {
+                    bounds = Some(match bounds
+                    {
+                        Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x + 1), y1.max(y + 1)),
+                        None => (x, y, x + 1, y + 1),
+                    });
+                }
+            }
+
+            let metrics = self.metrics.get(i).copied().unwrap_or_default();
+            let baseline = metrics.baseline.unwrap_or(self.baseline);
+            let (x0, y0, x1, y1) = bounds.unwrap_or((0, 0, 0, 0));
+            let advance = metrics.advance.unwrap_or(if bounds.is_some() { x1 + self.spacing } else { tw / 2 });
+
+            glyphs.push(PageGlyph
+            {
+                c,
+                glyph: Glyph
+                {
+                    width: x1 - x0,
+                    height: y1 - y0,
+                    x_offset: x0,
+                    y_offset: y0 - baseline,
+                    advance,
+                    bits: (y0..y1).flat_map(|y| (x0..x1).map(move |x| (x, y))).map(|(x, y)| set(x, y)).collect(),
+                },
+                x: cx + x0,
+                y: cy + y0,
+            });
+        }
+
+        glyphs
+    }
+
+    pub fn build(&self, doc: &Document, grid: &GridSettings) -> BitmapFont
+    {
+        let glyphs = self.glyphs(doc, grid);
+        let ascent = glyphs.iter().map(|g| -g.glyph.y_offset).max().unwrap_or(0).max(0);
+        let descent = glyphs.iter().map(|g| g.glyph.y_offset + g.glyph.height).max().unwrap_or(0).max(0);
+
+        BitmapFont
+        {
+            name: self.name.clone(),
+            ascent,
+            descent,
+            glyphs: glyphs.into_iter().map(|g| (g.c, g.glyph)).collect(),
+        }
+    }
+}
+
+//AngelCode BMFont description, the document itself is the only page
+pub fn to_bmfont(design: &FontDesign, doc: &Document, grid: &GridSettings, page: &str, xml: bool) -> String
+{
+    let font = design.build(doc, grid);
+    let glyphs = design.glyphs(doc, grid);
+    let line_height = font.line_height(0);
+    let char_line = |g: &PageGlyph|
+    {
+        //BMFont measures yoffset from the top of the line instead of the baseline
+        let values = [("id", g.c as i32), ("x", g.x), ("y", g.y), ("width", g.glyph.width), ("height", g.glyph.height), ("xoffset", g.glyph.x_offset), ("yoffset", font.ascent + g.glyph.y_offset), ("xadvance", g.glyph.advance), ("page", 0), ("chnl", 15)];
+        if xml
+        {
+            format!("    <char {}/>\n", values.iter().map(|(k, v)| format!("{}=\"{}\"", k, v)).collect::<Vec<String>>().join(" "))
+        }
+        else
+        {
+            format!("char {}\n", values.iter().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<String>>().join(" "))
+        }
+    };
+
+    let mut fnt = String::new();
+    if xml
+    {
+        let (name, page) = (xml_escape(&design.name), xml_escape(page));
+        fnt += "<?xml version=\"1.0\"?>\n<font>\n";
+        fnt += &format!("  <info face=\"{}\" size=\"{}\" bold=\"0\" italic=\"0\" charset=\"\" unicode=\"1\" stretchH=\"100\" smooth=\"0\" aa=\"1\" padding=\"0,0,0,0\" spacing=\"0,0\"/>\n", name, line_height);
+        fnt += &format!("  <common lineHeight=\"{}\" base=\"{}\" scaleW=\"{}\" scaleH=\"{}\" pages=\"1\" packed=\"0\"/>\n", line_height, font.ascent, doc.width, doc.height);
+        fnt += &format!("  <pages>\n    <page id=\"0\" file=\"{}\"/>\n  </pages>\n", page);
+        fnt += &format!("  <chars count=\"{}\">\n", glyphs.len());
+        fnt += &glyphs.iter().map(char_line).collect::<String>();
+        fnt += "  </chars>\n</font>\n";
+    }
+    else
+    {
+        let (name, page) = (text_attribute(&design.name), text_attribute(page));
+        fnt += &format!("info face=\"{}\" size={} bold=0 italic=0 charset=\"\" unicode=1 stretchH=100 smooth=0 aa=1 padding=0,0,0,0 spacing=0,0\n", name, line_height);
+        fnt += &format!("common lineHeight={} base={} scaleW={} scaleH={} pages=1 packed=0\n", line_height, font.ascent, doc.width, doc.height);
+        fnt += &format!("page id=0 file=\"{}\"\n", page);
+        fnt += &format!("chars count={}\n", glyphs.len());
+        fnt += &glyphs.iter().map(char_line).collect::<String>();
+    }
+
+    fnt
+}
+
+//The text format has no escapes, quotes and line breaks would end the value or the line
+fn text_attribute(text: &str) -> String
+{
+    text.chars().map(|c| match c
+    {
+        '"' => '\'',
+        c if c.is_control() => ' ',
+        c => c,
+    }).collect()
+}
+
+//For attribute values in double quotes
+fn xml_escape(text: &str) -> String
+{
+    text.chars().map(|c| match c
+    {
+        '&' => "&amp;".to_string(),
+        '<' => "&lt;".to_string(),
+        '>' => "&gt;".to_string(),
+        '"' => "&quot;".to_string(),
+        '\'' => "&apos;".to_string(),
+        _ => c.to_string(),
+    }).collect()
+}
+
+fn export_bmfont(design: &FontDesign, doc: &Document, grid: &GridSettings, xml: bool) -> Result<(), Box<dyn std::error::Error>>
+{
+    crate::export_as_png(&doc.to_image(), &design.path)?;
+    //The page is next to the .fnt file, so only the file name goes in
+    let page = std::path::Path::new(&design.path).file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default() + ".png";
+    std::fs::write(design.path.clone() + ".fnt", to_bmfont(design, doc, grid, &page, xml))?;
+    Ok(())
+}
+
+//Glyphs are drawn on the tile grid of the current document, the window sets the metrics, previews and exports
+pub fn font_window(egui_ctx: &egui::Context, design: &mut FontDesign, doc: &mut Document, grid: &GridSettings, text_tool: &mut TextTool)
+{
+    let mut open = design.open;
+    egui::Window::new("Font Mode").open(&mut open).show(egui_ctx, |ui|
+    {
+        ui.label("Every tile of the tile grid is one glyph");
+        ui.horizontal(|ui|
+        {
+            ui.label("Name");
+            ui.text_edit_singleline(&mut design.name);
+        });
+        ui.label("Characters in tile order");
+        ui.text_edit_singleline(&mut design.chars);
+        ui.horizontal(|ui|
+        {
+            ui.add(egui::DragValue::new(&mut design.baseline).clamp_range(0..=grid.tile_size.1).prefix("baseline "));
+            ui.add(egui::DragValue::new(&mut design.spacing).clamp_range(-8..=16).prefix("spacing "));
+        });
+
+        let count = design.chars.chars().count();
+        design.metrics.resize(count, GlyphMetrics::default());
+        ui.collapsing("Glyphs", |ui|
+        {
+            egui::ScrollArea::vertical().max_height(200.0).show(ui, |ui|
+            {
+                for (c, metrics) in design.chars.chars().zip(design.metrics.iter_mut())
+                {
+                    ui.horizontal(|ui|
+                    {
+                        ui.monospace(format!("{:?}", c));
+                        metric_ui(ui, &mut metrics.advance, 1, "advance ");
+                        metric_ui(ui, &mut metrics.baseline, design.baseline, "baseline ");
+                    });
+                }
+            });
+        });
+
+        let font = design.build(doc, grid);
+        ui.separator();
+        ui.label("Preview");
+        ui.text_edit_multiline(&mut design.sample);
+        ui.add(egui::Slider::new(&mut design.zoom, 1.0..=8.0).text("Zoom"));
+        let mask = font::render_text(&font, &design.sample, Align::Left, 0, 1);
+        if design.preview.as_ref().map(|p| p.0 != mask).unwrap_or(true)
+        {
+            let (w, h) = (mask.first().map(|r| r.len()).unwrap_or(0).max(1), mask.len().max(1));
+            let mut image = egui::ColorImage::new([w, h], egui::Color32::TRANSPARENT);
+            for (y, row) in mask.iter().enumerate()
+            {
+                for (x, _) in row.iter().enumerate().filter(|(_, set)| **set)
+                {
+                    image.pixels[y * w + x] = PREVIEW_COLOR;
+                }
+            }
+            design.preview = Some((mask, egui_ctx.load_texture("font preview", image, egui::TextureOptions::NEAREST)));
+        }
+        if let Some((_, texture)) = &design.preview
+        {
+            egui::ScrollArea::horizontal().show(ui, |ui|
+            {
+                ui.image(texture.id(), texture.size_vec2() * design.zoom);
+            });
+        }
+
+        if ui.button("Use in Text Tool").clicked()
+        {
+            match text_tool.fonts.iter().position(|f| f.name == font.name)
+            {
+                Some(i) => text_tool.fonts[i] = font.clone(),
+                None => text_tool.fonts.push(font.clone()),
+            }
+            text_tool.font = text_tool.fonts.iter().position(|f| f.name == font.name).unwrap_or(0);
+        }
+
+        ui.separator();
+        ui.label("Path: (without ending)");
+        ui.text_edit_singleline(&mut design.path);
+        ui.horizontal(|ui|
+        {
+            if ui.button("Export BMFont").clicked() && export_bmfont(design, doc, grid, false).is_err()
+            {
+                println!("Was not able to export font");
+            }
+            if ui.button("Export BMFont XML").clicked() && export_bmfont(design, doc, grid, true).is_err()
+            {
+                println!("Was not able to export font");
+            }
+            if ui.button("Export BDF").clicked() && std::fs::write(design.path.clone() + ".bdf", font::to_bdf(&font)).is_err()
+            {
+                println!("Was not able to export font");
+            }
+        });
+    });
+    design.open = open;
+}
+
+//Checkbox for automatic, otherwise a value that starts at the default
+fn metric_ui(ui: &mut egui::Ui, value: &mut Option<i32>, default: i32, prefix: &str)
+{
+    let mut auto = value.is_none();
+    if ui.checkbox(&mut auto, "auto").changed()
+    {
+        *value = if auto { None } else { Some(default) };
+    }
+    if let Some(v) = value
+    {
+        ui.add(egui::DragValue::new(v).clamp_range(-64..=64).prefix(prefix));
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn escapes_attributes()
+    {
+        assert_eq!(xml_escape("Pixel Font"), "Pixel Font");
+        assert_eq!(xml_escape("A&B <\"x\"> 'y'"), "A&amp;B &lt;&quot;x&quot;&gt; &apos;y&apos;");
+        assert_eq!(xml_escape("fönt.png"), "fönt.png");
+
+        //The text format can only keep the value on one line and inside its quotes
+        assert_eq!(text_attribute("Pixel Font"), "Pixel Font");
+        assert_eq!(text_attribute("My \"Big\"\nFont\r\t"), "My 'Big' Font  ");
+        assert_eq!(text_attribute("fönt.png"), "fönt.png");
+    }
+}
@@ -0,0 +1,159 @@
+use macroquad::prelude::*;
+use macroquad::window::miniquad::{BlendFactor, BlendState, BlendValue, Equation, PipelineParams};
+
+pub struct GridSettings
+{
+    pub show: bool,
+    pub color: [f32; 4],
+    pub min_scale: f32,//Pixel grid is hidden when zoomed out further than this
+
+    pub show_tiles: bool,
+    pub tile_size: (i32, i32),
+    pub tile_offset: (i32, i32),
+    pub tile_color: [f32; 4],
+    pub snap: bool,//Selection snaps to the tile grid
+}
+
+impl Default for GridSettings
+{
+    fn default() -> Self
+    {
+        GridSettings
+        {
+            show: false,
+            color: [0.0, 0.0, 0.0, 0.5],
+            min_scale: 4.0,
+            show_tiles: false,
+            tile_size: (16, 16),
+            tile_offset: (0, 0),
+            tile_color: [0.2, 0.8, 1.0, 0.8],
+            snap: false,
+        }
+    }
+}
+
+impl GridSettings
+{
+    //Edge position (between pixels) moved to the closest tile grid line, if snapping is on
+    pub fn snap(&self, edge: (i32, i32)) -> (i32, i32)
+    {
+        if !self.snap
+        {
+            return edge;
+        }
+
+        let snap_axis = |v: i32, size: i32, offset: i32| ((v - offset) as f32 / size as f32).round() as i32 * size + offset;
+        (snap_axis(edge.0, self.tile_size.0, self.tile_offset.0), snap_axis(edge.1, self.tile_size.1, self.tile_offset.1))
+    }
+}
+
+pub fn grid_ui(ui: &mut egui::Ui, settings: &mut GridSettings)
+{
+    ui.checkbox(&mut settings.show, "Pixel Grid");
+    ui.horizontal(|ui|
+    {
+        ui.color_edit_button_rgba_unmultiplied(&mut settings.color);
+        ui.add(egui::Slider::new(&mut settings.color[3], 0.0..=1.0).text("Opacity"));
+    });
+    ui.add(egui::Slider::new(&mut settings.min_scale, 0.125..=32.0).logarithmic(true).text("Hide below zoom"));
+
+    ui.checkbox(&mut settings.show_tiles, "Tile Grid");
+    ui.horizontal(|ui|
+    {
+        ui.label("Size");
+        ui.add(egui::DragValue::new(&mut settings.tile_size.0).clamp_range(1..=1024));
+        ui.add(egui::DragValue::new(&mut settings.tile_size.1).clamp_range(1..=1024));
+        ui.label("Offset");
+        ui.add(egui::DragValue::new(&mut settings.tile_offset.0));
+        ui.add(egui::DragValue::new(&mut settings.tile_offset.1));
+    });
+    ui.horizontal(|ui|
+    {
+        ui.color_edit_button_rgba_unmultiplied(&mut settings.tile_color);
+        ui.checkbox(&mut settings.snap, "Snap selection");
+    });
+}
+
+//rect is where the canvas is on screen, canvas its size in pixels
+pub fn draw_grids(material: Material, settings: &GridSettings, rect: Rect, canvas: (f32, f32), scale: f32)
+{
+    if settings.show && scale >= settings.min_scale
+    {
+        draw_lines(material, rect, canvas, (1.0, 1.0), (0.0, 0.0), scale, settings.color);
+    }
+
+    if settings.show_tiles
+    {
+        let cell = (settings.tile_size.0 as f32, settings.tile_size.1 as f32);
+        let offset = (settings.tile_offset.0 as f32, settings.tile_offset.1 as f32);
+        draw_lines(material, rect, canvas, cell, offset, scale, settings.tile_color);
+    }
+}
+
+const GRID_VERTEX: &str = r#"#version 100
+attribute vec3 position;
+attribute vec2 texcoord;
+
+varying highp vec2 uv;
+
+uniform mat4 Model;
+uniform mat4 Projection;
+
+void main() {
+    gl_Position = Projection * Model * vec4(position, 1);
+    uv = texcoord;
+}"#;
+
+//Draws a line wherever the fragment is closer than half a screen pixel to a cell border
+const GRID_FRAGMENT: &str = r#"#version 100
+precision highp float;
+
+varying highp vec2 uv;
+
+uniform vec2 canvas;
+uniform vec2 cell;
+uniform vec2 offset;
+uniform float scale;
+uniform vec4 line_color;
+
+void main() {
+    vec2 p = fract((uv * canvas - offset) / cell);
+    vec2 d = min(p, 1.0 - p) * cell * scale;
+    if (min(d.x, d.y) > 0.5) {
+        discard;
+    }
+    gl_FragColor = line_color;
+}"#;
+
+pub fn grid_material() -> Material
+{
+    load_material(GRID_VERTEX, GRID_FRAGMENT, MaterialParams
+    {
+        uniforms: vec![
+            ("canvas".to_string(), UniformType::Float2),
+            ("cell".to_string(), UniformType::Float2),
+            ("offset".to_string(), UniformType::Float2),
+            ("scale".to_string(), UniformType::Float1),
+            ("line_color".to_string(), UniformType::Float4),
+        ],
+        pipeline_params: PipelineParams
+        {
+            color_blend: Some(BlendState::new(Equation::Add, BlendFactor::Value(BlendValue::SourceAlpha), BlendFactor::OneMinusValue(BlendValue::SourceAlpha))),
+            ..Default::default()
+        },
+        ..Default::default()
+    }).unwrap()
+}
+
+//One quad for a whole grid
+fn draw_lines(material: Material, rect: Rect, canvas: (f32, f32), cell: (f32, f32), offset: (f32, f32), scale: f32, color: [f32; 4])
+{
+    gl_use_material(material);
+    material.set_uniform("canvas", vec2(canvas.0, canvas.1));
+    material.set_uniform("cell", vec2(cell.0, cell.1));
+    material.set_uniform("offset", vec2(offset.0, offset.1));
+    material.set_uniform("scale", scale);
+    material.set_uniform("line_color", vec4(color[0], color[1], color[2], color[3]));
+    draw_rectangle(rect.x, rect.y, rect.w, rect.h, WHITE);
+    gl_use_default_material();
+}
@@ -0,0 +1,189 @@
+use std::fs;
+
+use macroquad::prelude::*;
+
+const SETTINGS_PATH: &str = "settings.txt";
+const UI_SCALES: [f32; 6] = [0.75, 1.0, 1.25, 1.5, 2.0, 3.0];
+
+//Window and interface settings, kept between runs in settings.txt
+//The window position is not part of it: macroquad 0.3 (miniquad 0.3) can neither read nor set it,
+//so the system places the window on every start
+pub struct Layout
+{
+    pub fullscreen: bool,
+    pub window_size: (i32, i32),
+    pub docked: bool,//Side panels instead of floating windows
+    pub ui_scale: f32,
+    applied_scale: f32,//ui_scale egui is using right now
+    dpi_scale: f32,
+    changed: Option<f64>,//Time of the last change that is not saved yet
+}
+
+impl Default for Layout
+{
+    fn default() -> Self
+    {
+        Layout
+        {
+            fullscreen: false,
+            window_size: (1280, 800),
+            docked: true,
+            ui_scale: 1.0,
+            applied_scale: 1.0,
+            dpi_scale: 1.0,
+            changed: None,
+        }
+    }
+}
+
+impl Layout
+{
+    //Missing file or unknown lines fall back to the defaults
+    pub fn load() -> Layout
+    {
+        let mut layout = Layout::default();
+        let text = fs::read_to_string(SETTINGS_PATH).unwrap_or_default();
+
+        for line in text.lines()
+        {
+            let (key, value) = match line.split_once('=')
+            {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => continue,
+            };
+            match key
+            {
+                "fullscreen" => layout.fullscreen = value == "true",
+                "window_width" => layout.window_size.0 = value.parse().unwrap_or(layout.window_size.0),
+                "window_height" => layout.window_size.1 = value.parse().unwrap_or(layout.window_size.1),
+                "docked" => layout.docked = value == "true",
+                "ui_scale" => layout.ui_scale = value.parse().unwrap_or(1.0_f32).clamp(0.5, 4.0),
+                _ => {}
+            }
+        }
+
+        layout
+    }
+
+    pub fn save(&self)
+    {
+        let text = format!("fullscreen={}\nwindow_width={}\nwindow_height={}\ndocked={}\nui_scale={}\n",
+            self.fullscreen, self.window_size.0, self.window_size.1, self.docked, self.ui_scale);
+        if fs::write(SETTINGS_PATH, text).is_err()
+        {
+            println!("Could not save settings");
+        }
+    }
+
+    fn mark_changed(&mut self)
+    {
+        self.changed = Some(get_time());
+    }
+
+    //Remembers the window size, and saves once nothing changed for a second (resizing sends many sizes)
+    pub fn update(&mut self)
+    {
+        let size = (screen_width() as i32, screen_height() as i32);
+        if !self.fullscreen && size != self.window_size
+        {
+            self.window_size = size;
+            self.mark_changed();
+        }
+
+        if let Some(time) = self.changed
+        {
+            if get_time() - time > 1.0
+            {
+                self.save();
+                self.changed = None;
+            }
+        }
+    }
+
+    //egui starts out at the monitor scale, the ui scale is multiplied on top of that
+    pub fn apply_scale(&mut self, egui_ctx: &egui::Context)
+    {
+        self.dpi_scale = egui_ctx.pixels_per_point() / self.applied_scale;
+        if self.ui_scale != self.applied_scale
+        {
+            egui_ctx.set_pixels_per_point(self.dpi_scale * self.ui_scale);
+            self.applied_scale = self.ui_scale;
+        }
+    }
+
+    //Physical pixels per screen coordinate
+    pub fn dpi_scale(&self) -> f32
+    {
+        self.dpi_scale
+    }
+}
+
+pub fn layout_ui(ui: &mut egui::Ui, layout: &mut Layout)
+{
+    let before = (layout.fullscreen, layout.docked, layout.ui_scale);
+
+    ui.checkbox(&mut layout.fullscreen, "Fullscreen");
+    ui.label("The window size is remembered, its position is left to the system");
+    ui.checkbox(&mut layout.docked, "Docked panels");
+    ui.horizontal(|ui|
+    {
+        ui.label("UI Scale");
+        for scale in UI_SCALES
+        {
+            ui.selectable_value(&mut layout.ui_scale, scale, format!("{}%", scale * 100.0));
+        }
+    });
+
+    if layout.fullscreen != before.0
+    {
+        set_fullscreen(layout.fullscreen);
+        if !layout.fullscreen
+        {
+            request_new_screen_size(layout.window_size.0 as f32, layout.window_size.1 as f32);
+        }
+    }
+    if (layout.fullscreen, layout.docked, layout.ui_scale) != before
+    {
+        layout.mark_changed();
+    }
+}
+
+//Collapsing part of a side panel when docked, otherwise a floating window
+pub fn section(egui_ctx: &egui::Context, panel: Option<&mut egui::Ui>, title: &str, anchor: (egui::Align2, egui::Vec2), add_contents: impl FnOnce(&mut egui::Ui))
+{
+    match panel
+    {
+        Some(ui) =>
+        {
+            egui::CollapsingHeader::new(title).default_open(true).show(ui, add_contents);
+        }
+        None =>
+        {
+            egui::Window::new(title).anchor(anchor.0, anchor.1).show(egui_ctx, add_contents);
+        }
+    }
+}
+
+//Same as section, but the floating window has a close button, docked the caller's toggle hides it
+pub fn closable_section(egui_ctx: &egui::Context, panel: Option<&mut egui::Ui>, title: &str, open: &mut bool, add_contents: impl FnOnce(&mut egui::Ui))
+{
+    match panel
+    {
+        Some(ui) =>
+        {
+            egui::CollapsingHeader::new(title).default_open(true).show(ui, add_contents);
+        }
+        None =>
+        {
+            egui::Window::new(title).open(open).show(egui_ctx, add_contents);
+        }
+    }
+}
+
+//Screen area that is not covered by docked panels, call after all panels were added
+pub fn workspace(egui_ctx: &egui::Context) -> Rect
+{
+    let scale = screen_width() / egui_ctx.screen_rect().width();
+    let area = egui_ctx.available_rect();
+    Rect::new(area.min.x * scale, area.min.y * scale, area.width() * scale, area.height() * scale)
+}
@@ -1,20 +1,70 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
-use std::fs::{self, File};
-use std::io::{Write, Read};
-use std::path::PathBuf;
+use std::fs::File;
+use std::io::{ErrorKind, Write, Read};
 use image::error::ImageFormatHint;
-use image::{DynamicImage, ImageBuffer, Rgba};
-use egui::Pos2;
-use egui_file::FileDialog;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, DynamicImage, Frame, ImageBuffer, Rgba, RgbaImage};
 use macroquad::prelude::*;
 
+mod adjustments;
+mod analysis;
+mod backdrop;
+mod canvas;
+mod color;
+mod document;
+mod effects;
+mod font;
+mod font_mode;
+mod grid;
+mod layout;
+mod noise;
+mod palette;
+mod pixelate;
+mod preview;
+mod reference;
+mod rng;
+mod rulers;
+mod selection;
+mod spray;
+mod status_bar;
+mod text;
+mod transform;
+mod view_filter;
+mod viewport;
+
+use adjustments::Adjustments;
+use analysis::AnalysisSettings;
+use backdrop::Backdrop;
+use canvas::Canvas;
+use document::{Document, SplitView};
+use effects::EffectSettings;
+use font_mode::FontDesign;
+use grid::GridSettings;
+use layout::Layout;
+use noise::GeneratorSettings;
+use palette::{ColorSliders, Palette, RampSettings};
+use pixelate::Pixelate;
+use preview::Preview;
+use reference::{Reference, ReferencePanel};
+use rulers::Guides;
+use selection::Selection;
+use text::TextTool;
+use transform::{FreeTransform, SizeDialog, TrimSettings};
+use view_filter::ViewFilter;
+
 fn window_conf() -> Conf
 {
+    let layout = Layout::load();
+
     Conf 
     {
         window_title: "editor".to_owned(),
-        fullscreen: true,
+        fullscreen: layout.fullscreen,
+        window_width: layout.window_size.0,
+        window_height: layout.window_size.1,
+        window_resizable: true,
+        high_dpi: true,
         ..Default::default()
     }
 }
@@ -23,39 +73,55 @@ fn window_conf() -> Conf
 async fn main() 
 {
     let mut rgba: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+    let mut color_sliders = ColorSliders::default();
+    let mut cycle_preview = true;
+    let mut ramp = RampSettings::default();
 
     let mut width_str = String::new();
     let mut height_str = String::new();
 
-    let mut clicked: bool = false;
-
-    let mut width: i32 = 0;
-    let mut height: i32 = 0;
+    let mut documents: Vec<Document> = Vec::new();
+    let mut current = 0;
+    let mut split = SplitView::default();
 
-    let mut x = 0.0;
-    let mut y = 0.0;
-    let mut canvas_width = 0.0;
-    let mut canvas_height = 0.0;
-    let original_grid_size = 20.0;
-    let mut grid_size = original_grid_size;
+    let mut layout = Layout::load();
+    let mut workspace = Rect::new(0.0, 0.0, screen_width(), screen_height());//Part of the screen not covered by panels
     
-    let mut show_grid: bool = false;
-
-    let mut zoom: f32 = 1.0;
-    let mut camera: (f32, f32) = (0.0, 0.0);
+    let mut grid = GridSettings::default();
+    let mut guides = Guides::default();
 
-    let mut mouse_pos1: (f32, f32);
-    let mut mouse_middle = false;
-
-    let mut pixels: Vec<Vec<(u8, u8, u8, u8)>> = Vec::new();
+    let mut mouse_pos1: (f32, f32) = (0.0, 0.0);
+    let mut mouse_middle = false;//panning with the middle mouse button or space + left mouse button
+    let mut scroll_pans = false;//for trackpads: scrolling pans, ctrl + scrolling zooms
+    let mut keep_on_screen = true;
+    let mut over_ui = false;//mouse is over a window, so it should not paint or zoom the canvas
+    let mut typing = false;//a text field has focus, so shortcuts are ignored
 
     let mut save_path = String::new();
     let mut load_path = String::new();
 
-    let mut color_picker = false;
-    let mut eraser = false;
-    let mut fill_tool = false;
-    let mut brush_size = 0;
+    let mut analysis = AnalysisSettings::default();
+    let mut preview = Preview::default();
+    let mut size_dialog = SizeDialog::default();
+    let mut free_transform = FreeTransform::default();
+    let mut trim = TrimSettings::default();
+    let mut adjustments = Adjustments::default();
+    let mut effects = EffectSettings::default();
+    let mut pixelate = Pixelate::default();
+    let mut generator = GeneratorSettings::default();
+    let mut text_tool = TextTool::default();
+    let mut font_design = FontDesign::default();
+
+    let mut reference_panel = ReferencePanel::default();
+    let mut reference_drag: Option<usize> = None;
+    let mut reference_mouse: (f32, f32) = (0.0, 0.0);
+
+    let mut view_filter = ViewFilter::None;
+    let mut side_by_side = false;
+
+    let grid_material = grid::grid_material();
+    let checker_material = backdrop::checker_material();
+    let mut backdrop = Backdrop::default();
 
     loop 
     {
@@ -65,344 +131,645 @@ async fn main()
 
         //Update
 
-        //Zoom
-        if mouse_wheel().1 > 0.0
-        {
-            let z = zoom_in(zoom, camera);
-            zoom = z.0;
-            camera = z.1;
-            grid_size *= 2.0;
-        }
-        else if mouse_wheel().1 < 0.0
-        {
-            let z = zoom_out(zoom, camera);
-            zoom = z.0;
-            camera = z.1;
-            grid_size *= 0.5;
-        }
+        layout.update();
 
-        //Movement
+        let wheel = mouse_wheel();
+        let ctrl = is_key_down(KeyCode::LeftControl) || is_key_down(KeyCode::RightControl);
+        let shift = is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift);
+        let space = is_key_down(KeyCode::Space) && !typing;//Spaces typed into a text field don't pan
 
-        //Keyboard
-        if is_key_down(KeyCode::Left)
-        {
-            camera.0 -= 5.0;
-        }
-        if is_key_down(KeyCode::Up)
-        {
-            camera.1 -= 5.0;
-        }
-        if is_key_down(KeyCode::Right)
-        {
-            camera.0 += 5.0;
-        }
-        if is_key_down(KeyCode::Down)
+        //Everything below works on the pane under the mouse
+        let right = split.enabled && mouse_position().0 >= split.split_x(workspace);
+        let hovered = if right { split.document } else { current };
+
+        if let Some(doc) = documents.get_mut(hovered)
         {
-            camera.1 += 5.0;
-        }
+            let viewport = if right { &mut split.viewport } else { &mut doc.viewport };
 
-        //Mouse
-        // if is_mouse_button_pressed(MouseButton::Middle)
-        // {
-        //     mouse_pos1 = mouse_position();
-        //     mouse_middle = true;
-        // }
+            //Zoom (around the mouse, so the pixel under it stays in place)
+            if wheel.1 != 0.0 && (!scroll_pans || ctrl) && !over_ui
+            {
+                viewport.zoom_by(1.2_f32.powf(wheel.1.signum()), mouse_position());
+            }
 
-        // if is_mouse_button_down(MouseButton::Middle) && mouse_middle
-        // {
+            //Movement
 
-        // }
+            //Scrolling (two finger scrolling on trackpads, or a horizontal mouse wheel)
+            if scroll_pans && !ctrl && !over_ui
+            {
+                viewport.pan(wheel.0 * 20.0, wheel.1 * 20.0);
+            }
+            else if wheel.0 != 0.0 && !over_ui
+            {
+                viewport.pan(wheel.0 * 20.0, 0.0);
+            }
 
-        // if is_mouse_button_released(MouseButton::Middle)
-        // {
-        //     mouse_middle = false;
-        // }
+            //Keyboard (same speed at any frame rate, faster when zoomed in), arrow keys in a text field move the cursor instead
+            let key_pan = (30.0 * viewport.scale).max(300.0) * get_frame_time();
+            if !typing
+            {
+                if is_key_down(KeyCode::Left)
+                {
+                    viewport.pan(key_pan, 0.0);
+                }
+                if is_key_down(KeyCode::Up)
+                {
+                    viewport.pan(0.0, key_pan);
+                }
+                if is_key_down(KeyCode::Right)
+                {
+                    viewport.pan(-key_pan, 0.0);
+                }
+                if is_key_down(KeyCode::Down)
+                {
+                    viewport.pan(0.0, -key_pan);
+                }
+            }
+
+            //Mouse
+            if !over_ui && (is_mouse_button_pressed(MouseButton::Middle) || (space && is_mouse_button_pressed(MouseButton::Left)))
+            {
+                mouse_pos1 = mouse_position();
+                mouse_middle = true;
+            }
+
+            if mouse_middle && (is_mouse_button_down(MouseButton::Middle) || (space && is_mouse_button_down(MouseButton::Left)))
+            {
+                let mouse_pos2 = mouse_position();
+                viewport.pan(mouse_pos2.0 - mouse_pos1.0, mouse_pos2.1 - mouse_pos1.1);
+                mouse_pos1 = mouse_pos2;
+            }
+            else
+            {
+                mouse_middle = false;
+            }
+
+            //Undo (ctrl + z) and redo (ctrl + y or ctrl + shift + z)
+            if ctrl && !typing
+            {
+                if is_key_pressed(KeyCode::Z) && !shift
+                {
+                    doc.undo();
+                }
+                else if is_key_pressed(KeyCode::Y) || (is_key_pressed(KeyCode::Z) && shift)
+                {
+                    doc.redo();
+                }
+            }
+        }
 
 
         //GUI
         egui_macroquad::ui(|egui_ctx| 
         {
-            egui::Window::new("File").anchor(egui::Align2::LEFT_CENTER, egui::vec2(50.0, -250.0)).show(egui_ctx, |ui|
-            {
-                ui.label("Path: (without ending)");
-                ui.text_edit_singleline(&mut save_path);
-                if ui.button("Save").clicked()
-                {
-                    let width = width as u16;
-                    let height = height as u16;
-                    let pixels = two_to_one(pixels.to_vec());
-                    let image = Image::new(width, height, pixels);
+            layout.apply_scale(egui_ctx);
+            over_ui = egui_ctx.wants_pointer_input() || egui_ctx.is_pointer_over_area();
+            typing = egui_ctx.wants_keyboard_input();
 
-                    save(&image, &save_path);
-                }
-                ui.spacing();
-                ui.separator();
-                ui.spacing();
-                ui.label("Path: (without ending)");
-                ui.text_edit_singleline(&mut load_path);
-                if ui.button("Load").clicked()
+            //File, New Image and Tools, in the left panel when docked
+            let docked = layout.docked;
+            let mut sections = |mut panel: Option<&mut egui::Ui>|
+            {
+                layout::section(egui_ctx, panel.as_deref_mut(), "File", (egui::Align2::LEFT_CENTER, egui::vec2(50.0, -250.0)), |ui|
                 {
-                    let image = load(&mut load_path);
+                    ui.label("Path: (without ending)");
+                    ui.text_edit_singleline(&mut save_path);
+                    if ui.button("Save").clicked()
+                    {
+                        if let Some(doc) = documents.get_mut(current)
+                        {
+                            save(&doc.to_image(), &save_path);
+                            doc.name = save_path.clone();
+                        }
+                    }
+                    ui.spacing();
+                    ui.separator();
+                    ui.spacing();
+                    ui.label("Path: (without ending)");
+                    ui.text_edit_singleline(&mut load_path);
+                    if ui.button("Load").clicked()
+                    {
+                        let image = load(&load_path);
 
-                    if image.is_ok()
+                        if let Ok(image) = image
+                        {
+                            documents.push(Document::from_image(&load_path, image));
+                            current = documents.len() - 1;
+                            documents[current].fit(workspace);
+                        }
+                        else 
+                        {
+                            println!("Was not able to load file");
+                        }
+                    }
+                    ui.spacing();
+                    ui.separator();
+                    ui.spacing();
+                    ui.label("Path: (without ending)");
+                    ui.text_edit_singleline(&mut save_path);
+                    if let Some(doc) = documents.get(current)
                     {
-                        let image = image.unwrap();
-                        
-                        width = image.width as i32;
-                        height = image.height as i32;
-
-                        canvas_width = width as f32 * original_grid_size;
-                        canvas_height = height as f32 * original_grid_size;
-                        x = screen_width()/2.0-canvas_width/2.0;
-                        y = screen_height()/2.0-canvas_height/2.0;
-                        pixels.clear();
-
-                        pixels = one_to_two(image.pixels, height as usize, width as usize);
-                        clicked = true;
+                        ui.horizontal(|ui|
+                        {
+                            if ui.button("Export").clicked()
+                            {
+                                let png = if trim.on_export { export_trimmed_png(doc, trim.background, &save_path) } else { export_as_png(&doc.to_image(), &save_path) };
+                                if png.is_err()
+                                {
+                                    println!("Could not export");
+                                }
+                            }
+                            ui.checkbox(&mut trim.on_export, "Trim");
+                        });
+                        if !doc.palette.cycles.is_empty()
+                        {
+                            ui.horizontal(|ui|
+                            {
+                                if ui.button("Export GIF").clicked() && export_cycle_gif(&doc.to_image(), &save_path).is_err()
+                                {
+                                    println!("Could not export gif");
+                                }
+                                if ui.button("Export PNG Sequence").clicked() && export_cycle_png_sequence(&doc.to_image(), &save_path).is_err()
+                                {
+                                    println!("Could not export png sequence");
+                                }
+                            });
+                        }
+                    }
+                    ui.spacing();
+                    ui.separator();
+                    ui.spacing();
+                    ui.label("Path: (without ending)");
+                    ui.text_edit_singleline(&mut load_path);
+                    if ui.button("Import").clicked()
+                    {
+                        let image = import_from_png(&load_path);
+
+                        if let Ok(image) = image
+                        {
+                            documents.push(Document::from_image(&load_path, image));
+                            current = documents.len() - 1;
+                            documents[current].fit(workspace);
+                        }
+                        else 
+                        {
+                            println!("Was not able to import image");
+                        }
                     }
-                    else 
+                    if ui.button("Pixelate Image").clicked()
                     {
-                        println!("Was not able to load file");
+                        pixelate.open = !pixelate.open;
+                        pixelate.path = load_path.clone();
                     }
-                }
-                ui.spacing();
-                ui.separator();
-                ui.spacing();
-                ui.label("Path: (without ending)");
-                ui.text_edit_singleline(&mut save_path);
-                if ui.button("Export").clicked()
+                });
+            
+                layout::section(egui_ctx, panel.as_deref_mut(), "New Image", (egui::Align2::LEFT_CENTER, egui::vec2(50.0, -25.0)), |ui|
                 {
-                    let width = width as u16;
-                    let height = height as u16;
-                    let pixels = two_to_one(pixels.to_vec());
-                    let image = Image::new(width, height, pixels);
+                    ui.label("Width:");
+                    ui.text_edit_singleline(&mut width_str);
+                    ui.label("Height");
+                    ui.text_edit_singleline(&mut height_str);
+                    if ui.button("Create").clicked()
+                    {
+                        let width = width_str.parse().unwrap();
+                        let height = height_str.parse().unwrap();
 
-                    let png = export_as_png(&image, &save_path);
-                    if png.is_ok()
+                        documents.push(Document::blank("", width, height));
+                        current = documents.len() - 1;
+                        documents[current].fit(workspace);
+                    }
+                });
+            
+                layout::section(egui_ctx, panel.as_deref_mut(), "Tools", (egui::Align2::LEFT_CENTER, egui::vec2(50.0, 150.0)), |ui|
+                {
+                    ui.color_edit_button_rgba_unmultiplied(&mut rgba);
+                    palette::color_model_sliders(ui, &mut rgba, &mut color_sliders);
+                    ui.spacing();
+                    if let Some(doc) = documents.get_mut(current)
                     {
-                        png.unwrap();
+                        let tools = &mut doc.tools;
+                        let response = ui.add(egui::Slider::new(&mut tools.brush_size, 1..=50));
+                        response.on_hover_text("BrushSize");
+                        ui.spacing();
+                        if ui.button("ColorPicker").clicked()
+                        {
+                            tools.color_picker = !tools.color_picker;
+                        }
+                        ui.spacing();
+                        if ui.button("Eraser").clicked()
+                        {
+                            tools.eraser = !tools.eraser;
+                        }
+                        ui.spacing();
+                        if ui.button("Filltool").clicked()
+                        {
+                            tools.fill_tool = !tools.fill_tool;
+                        }
+                        ui.spacing();
+                        if ui.button("Spray").clicked()
+                        {
+                            tools.spray.active = !tools.spray.active;
+                        }
+                        if tools.spray.active
+                        {
+                            spray::spray_ui(ui, &mut tools.spray);
+                        }
+                        ui.spacing();
+                        if ui.button("Text").clicked()
+                        {
+                            tools.text = !tools.text;
+                        }
+                        if tools.text
+                        {
+                            text::text_ui(ui, &mut text_tool);
+                        }
+                        ui.separator();
+                        ui.spacing();
+                        if ui.button("Select").clicked()
+                        {
+                            tools.select_tool = !tools.select_tool;
+                        }
+                        ui.separator();
+                        ui.spacing();
+                        ui.horizontal(|ui|
+                        {
+                            if ui.add_enabled(doc.history.can_undo(), egui::Button::new("Undo")).clicked()
+                            {
+                                doc.undo();
+                            }
+                            if ui.add_enabled(doc.history.can_redo(), egui::Button::new("Redo")).clicked()
+                            {
+                                doc.redo();
+                            }
+                        });
+                        transform::size_ui(ui, &mut size_dialog, doc);
+                        transform::transform_ui(ui, &mut free_transform, doc);
+                        transform::crop_ui(ui, &mut trim, doc);
+                        effects::effects_ui(ui, &mut effects, doc);
+                        noise::generate_ui(ui, &mut generator, doc, rgba);
+                        ui.separator();
+                        ui.spacing();
                     }
-                    else
+                    if ui.button("Show Grid").clicked()
                     {
-                        println!("Could not export");
+                        grid.show = !grid.show;
                     }
-                }
-                ui.spacing();
-                ui.separator();
-                ui.spacing();
-                ui.label("Path: (without ending)");
-                ui.text_edit_singleline(&mut load_path);
-                if ui.button("Import").clicked()
-                {
-                    let image = import_from_png(&load_path);
-
-                    if image.is_ok()
+                    ui.collapsing("Grid Settings", |ui|
+                    {
+                        grid::grid_ui(ui, &mut grid);
+                    });
+                    ui.collapsing("Background", |ui|
                     {
-                        let image = image.unwrap();
-                        
-                        width = image.width as i32;
-                        height = image.height as i32;
-
-                        canvas_width = width as f32 * original_grid_size;
-                        canvas_height = height as f32 * original_grid_size;
-                        x = screen_width()/2.0-canvas_width/2.0;
-                        y = screen_height()/2.0-canvas_height/2.0;
-                        pixels.clear();
-
-                        pixels = one_to_two(image.pixels, height as usize, width as usize);
-                        clicked = true;
+                        backdrop::backdrop_ui(ui, &mut backdrop);
+                    });
+                    ui.spacing();
+                    view_filter::view_filter_ui(ui, &mut view_filter, &mut side_by_side);
+                    ui.spacing();
+                    rulers::guides_ui(ui, &mut guides);
+                    ui.spacing();
+                    if let Some(doc) = documents.get_mut(current)
+                    {
+                        let left_pane = (workspace.x, workspace.y, split.split_x(workspace) - workspace.x, workspace.h);
+                        viewport::zoom_ui(ui, &mut doc.viewport, (doc.width as f32, doc.height as f32), left_pane);
                     }
-                    else 
+                    ui.checkbox(&mut scroll_pans, "Scroll to pan (trackpad)");
+                    ui.checkbox(&mut keep_on_screen, "Keep canvas on screen");
+                    ui.collapsing("Layout", |ui|
+                    {
+                        layout::layout_ui(ui, &mut layout);
+                    });
+                    ui.spacing();
+                    if ui.button("Analysis").clicked()
                     {
-                        println!("Was not able to import image");
+                        analysis.open = !analysis.open;
                     }
-                }
-            });
-            
-            egui::Window::new("New Image").anchor(egui::Align2::LEFT_CENTER, egui::vec2(50.0, -25.0)).show(egui_ctx, |ui|
-            {
-                ui.label("Width:");
-                ui.text_edit_singleline(&mut width_str);
-                ui.label("Height");
-                ui.text_edit_singleline(&mut height_str);
-                if ui.button("Create").clicked()
-                {
-                    width = width_str.parse().unwrap();
-                    height = height_str.parse().unwrap();
-
-                    canvas_width = width as f32 * original_grid_size;
-                    canvas_height = height as f32 * original_grid_size;
-                    x = screen_width()/2.0-canvas_width/2.0;
-                    y = screen_height()/2.0-canvas_height/2.0;
-                    pixels.clear();
-
-                    for i in 0..height
+                    if ui.button("Preview").clicked()
+                    {
+                        preview.open = !preview.open;
+                    }
+                    if ui.button("References").clicked()
+                    {
+                        reference_panel.open = !reference_panel.open;
+                    }
+                    if ui.button("Adjustments").clicked()
                     {
-                        let mut temp_vec: Vec<(u8, u8, u8, u8)> = Vec::new();
-                        for j in 0..width
+                        adjustments.open = !adjustments.open;
+                    }
+                    if ui.button("Font Mode").clicked()
+                    {
+                        font_design.open = !font_design.open;
+                        grid.show_tiles |= font_design.open;
+                    }
+                    if let Some(doc) = documents.get_mut(current)
+                    {
+                        if let Some(s) = &doc.selection
                         {
-                            temp_vec.push((0, 0, 0, 0));
+                            ui.label(format!("Selected: {} pixels", s.count()));
+                            if ui.button("Deselect").clicked()
+                            {
+                                doc.selection = None;
+                            }
                         }
-                        pixels.push(temp_vec);
                     }
-                    clicked = true;
-                }
-            });
-            
-            egui::Window::new("Tools").anchor(egui::Align2::LEFT_CENTER, egui::vec2(50.0, 150.0)).show(egui_ctx, |ui|
+                    ui.spacing();
+                });
+            };
+            if docked
             {
-                ui.color_edit_button_rgba_unmultiplied(&mut rgba);
-                ui.spacing();
-                let response = ui.add(egui::Slider::new(&mut brush_size, 1..=50));
-                response.on_hover_text("BrushSize");
-                ui.spacing();
-                if ui.button("ColorPicker").clicked()
+                egui::SidePanel::left("side_panel").show(egui_ctx, |ui|
                 {
-                    color_picker = !color_picker;
-                }
-                ui.spacing();
-                if ui.button("Eraser").clicked()
-                {
-                    eraser = !eraser;
-                }
-                ui.spacing();
-                if ui.button("Filltool").clicked()
+                    egui::ScrollArea::vertical().show(ui, |ui| sections(Some(ui)));
+                });
+            }
+            else
+            {
+                sections(None);
+            }
+
+            if let Some(doc) = documents.get_mut(current)
+            {
+                if docked
                 {
-                    fill_tool = !fill_tool;
+                    egui::SidePanel::right("palette_panel").show(egui_ctx, |ui|
+                    {
+                        palette::palette_window(egui_ctx, Some(ui), &mut doc.palette, &mut rgba, &mut ramp, &mut cycle_preview);
+                        preview::preview_window(egui_ctx, Some(ui), &mut preview, doc, &mut cycle_preview);
+                    });
                 }
-                ui.separator();
-                ui.spacing();
-                if ui.button("Show Grid").clicked()
+                else
                 {
-                    show_grid = !show_grid;
+                    palette::palette_window(egui_ctx, None, &mut doc.palette, &mut rgba, &mut ramp, &mut cycle_preview);
                 }
-                ui.spacing();
-                if ui.button("Reset Zoom").clicked()
+            }
+
+            //Panels first, so the workspace is known before the floating windows
+            if let Some(doc) = documents.get(hovered)
+            {
+                let viewport = if right { &split.viewport } else { &doc.viewport };
+                status_bar::status_bar(egui_ctx, &doc.pixels, viewport.screen_to_pixel(mouse_position()), &doc.selection, viewport, (doc.width, doc.height));
+            }
+            document::tab_bar(egui_ctx, &mut documents, &mut current, &mut split, workspace);
+            workspace = layout::workspace(egui_ctx);
+
+            if let Some(doc) = documents.get_mut(current)
+            {
+                analysis::analysis_window(egui_ctx, doc, &mut analysis);
+                reference::reference_window(egui_ctx, &mut reference_panel, &mut doc.references, &mut rgba);
+
+                if !docked
                 {
-                    zoom = 1.0;
-                    camera = (0.0, 0.0);
+                    preview::preview_window(egui_ctx, None, &mut preview, doc, &mut cycle_preview);
                 }
-                ui.spacing();
-            });
+            }
+            adjustments::adjustments_window(egui_ctx, &mut adjustments, &mut documents, current);
+            if let Some(doc) = documents.get_mut(current)
+            {
+                font_mode::font_window(egui_ctx, &mut font_design, doc, &grid, &mut text_tool);
+            }
+
+            let palette = documents.get(current).map(|d| d.palette.clone()).unwrap_or_default();
+            if let Some(doc) = pixelate::pixelate_window(egui_ctx, &mut pixelate, &palette)
+            {
+                documents.push(doc);
+                current = documents.len() - 1;
+                documents[current].fit(workspace);
+            }
         });
 
 
-        if clicked
+        if let Some(doc) = documents.get_mut(hovered)
         {
-            let p = (x-camera.0, y-camera.1);
-            let draw_size = (canvas_width*zoom, canvas_height*zoom);
+            let viewport = if right { split.viewport } else { doc.viewport };
+            let (width, height) = (doc.width, doc.height);
 
-            if is_mouse_button_down(MouseButton::Left)
+            if !over_ui && !space
             {
-                let grid = to_grid(mouse_position(), x, y, camera, zoom, grid_size);
+                reference::drag_references(&mut doc.references, &viewport, &mut reference_drag, reference_mouse);
+            }
+            reference_mouse = mouse_position();
+            let main_pane = split.panes(current, workspace)[0].0;
+            let on_rulers = !over_ui && !right && rulers::update_guides(&mut guides, &viewport, main_pane);
+
+            let tools = &mut doc.tools;
+            if !is_mouse_button_down(MouseButton::Left)
+            {
+                tools.stroke = false;
+            }
+
+            if reference_drag.is_some() || on_rulers
+            {
+                //Moving a reference or a guide, no painting
+            }
+            //Rectangle selection, works on pixel edges so it can also be dragged from outside of the canvas
+            else if tools.select_tool && !space && !mouse_middle
+            {
+                let c = viewport.screen_to_canvas(mouse_position());
+                let edge = guides.snap(c, viewport.scale, grid.snap((c.0.round() as i32, c.1.round() as i32)));
+                let edge = (edge.0.clamp(0, width), edge.1.clamp(0, height));
+
+                if is_mouse_button_pressed(MouseButton::Left) && !over_ui
+                {
+                    tools.select_start = Some(edge);
+                }
+                if let Some(start) = tools.select_start
+                {
+                    if is_mouse_button_down(MouseButton::Left)
+                    {
+                        let (x0, x1) = (start.0.min(edge.0), start.0.max(edge.0));
+                        let (y0, y1) = (start.1.min(edge.1), start.1.max(edge.1));
+                        doc.selection = if x0 < x1 && y0 < y1 { Some(Selection::from_rect(width as usize, height as usize, x0, y0, x1, y1)) } else { None };
+                    }
+                    else
+                    {
+                        tools.select_start = None;
+                    }
+                }
+            }
+            else if tools.text && !space && !mouse_middle
+            {
+                let grid = viewport.screen_to_pixel(mouse_position());
+                if is_mouse_button_pressed(MouseButton::Left) && !over_ui
+                {
+                    doc.stamp_text(&text_tool, grid, color::Color::from_array(rgba));
+                }
+            }
+            else if is_mouse_button_down(MouseButton::Left) && !space && !mouse_middle && !over_ui
+            {
+                let grid = viewport.screen_to_pixel(mouse_position());
                 if !(grid.0 < 0 || grid.0 > width-1 || grid.1 < 0 || grid.1 > height-1)
                 {
-                    if color_picker
+                    if tools.color_picker
                     {
-                        rgba = u8_to_rgba(pixels[grid.1 as usize][grid.0 as usize]);
-                        color_picker = false;
+                        rgba = color::Color::from_u8(doc.pixels[grid.1 as usize][grid.0 as usize]).to_array();
+                        tools.color_picker = false;
                     }
-                    else if eraser
+                    else
                     {
-                        let half_brush_size = brush_size / 2;
-                        for y in (grid.1 as isize - half_brush_size as isize)..(grid.1 as isize + half_brush_size as isize + 1) 
+                        //One undo step per stroke
+                        if !tools.stroke
+                        {
+                            tools.stroke = true;
+                            doc.checkpoint();
+                        }
+                        let tools = &mut doc.tools;
+                        let pixels = &mut doc.pixels;
+                        let selection = &doc.selection;
+
+                        if tools.eraser
                         {
-                            for x in (grid.0 as isize - half_brush_size as isize)..(grid.0 as isize + half_brush_size as isize + 1) 
+                            let half_brush_size = tools.brush_size / 2;
+                            doc.canvas.mark_dirty(grid.0 - half_brush_size, grid.1 - half_brush_size, half_brush_size * 2 + 1, half_brush_size * 2 + 1);
+                            for y in (grid.1 as isize - half_brush_size as isize)..(grid.1 as isize + half_brush_size as isize + 1) 
                             {
-                                if x >= 0 && x < width as isize && y >= 0 && y < height as isize 
+                                for x in (grid.0 as isize - half_brush_size as isize)..(grid.0 as isize + half_brush_size as isize + 1) 
                                 {
-                                    let x = x as usize;
-                                    let y = y as usize;
-                                    pixels[y][x] = (0, 0, 0, 0);
+                                    if x >= 0 && x < width as isize && y >= 0 && y < height as isize && selection::editable(selection, x as i32, y as i32)
+                                    {
+                                        let x = x as usize;
+                                        let y = y as usize;
+                                        pixels[y][x] = (0, 0, 0, 0);
+                                    }
                                 }
                             }
                         }
-                    }
-                    else if fill_tool 
-                    {
-                        let target_color = u8_to_rgba(pixels[grid.1 as usize][grid.0 as usize]);
-                        
-                        let mut stack = Vec::new();
-                        stack.push(grid);
-
-                        while let Some((x, y)) = stack.pop()
+                        else if tools.fill_tool 
                         {
-                            if pixels[y as usize][x as usize] != rgba_to_u8(target_color)
+                            let target_color = color::Color::from_u8(pixels[grid.1 as usize][grid.0 as usize]);
+                            let fill_color = color::Color::from_array(rgba).to_u8();
+                            
+                            let mut stack = Vec::new();
+                            if target_color.to_u8() != fill_color
                             {
-                                continue;
+                                stack.push(grid);
                             }
 
-                            pixels[y as usize][x as usize] = rgba_to_u8(rgba);
-
-                            if x > 0 
-                            {
-                                stack.push((x - 1, y));
-                            }
-                            if x < width - 1 
+                            while let Some((x, y)) = stack.pop()
                             {
-                                stack.push((x + 1, y));
-                            }
-                            if y > 0 
-                            {
-                                stack.push((x, y - 1));
-                            }
-                            if y < height - 1 
-                            {
-                                stack.push((x, y + 1));
+                                if !color::Color::from_u8(pixels[y as usize][x as usize]).matches(target_color, 0.0) || !selection::editable(selection, x, y)
+                                {
+                                    continue;
+                                }
+
+                                pixels[y as usize][x as usize] = fill_color;
+
+                                if x > 0 
+                                {
+                                    stack.push((x - 1, y));
+                                }
+                                if x < width - 1 
+                                {
+                                    stack.push((x + 1, y));
+                                }
+                                if y > 0 
+                                {
+                                    stack.push((x, y - 1));
+                                }
+                                if y < height - 1 
+                                {
+                                    stack.push((x, y + 1));
+                                }
                             }
+                            doc.canvas.mark_all_dirty();
+                            tools.fill_tool = false;
                         }
-                        fill_tool = false;
-                    }
-                    else 
-                    {
-                        // pixels[grid.1 as usize][grid.0 as usize] = rgba_to_u8(rgba);
-                        let half_brush_size = brush_size / 2;
-                        for y in (grid.1 as isize - half_brush_size as isize)..(grid.1 as isize + half_brush_size as isize + 1) 
+                        else if tools.spray.active
                         {
-                            for x in (grid.0 as isize - half_brush_size as isize)..(grid.0 as isize + half_brush_size as isize + 1) 
+                            let rgba = [color::Color::from_array(rgba)];
+                            let colors = if tools.spray.use_palette && !doc.palette.colors.is_empty() { &doc.palette.colors[..] } else { &rgba[..] };
+                            let (x, y, w, h) = tools.spray.spray(pixels, selection, grid, tools.brush_size / 2, colors, get_frame_time());
+                            doc.canvas.mark_dirty(x, y, w, h);
+                        }
+                        else 
+                        {
+                            // pixels[grid.1 as usize][grid.0 as usize] = color::Color::from_array(rgba).to_u8();
+                            let half_brush_size = tools.brush_size / 2;
+                            doc.canvas.mark_dirty(grid.0 - half_brush_size, grid.1 - half_brush_size, half_brush_size * 2 + 1, half_brush_size * 2 + 1);
+                            for y in (grid.1 as isize - half_brush_size as isize)..(grid.1 as isize + half_brush_size as isize + 1) 
                             {
-                                if x >= 0 && x < width as isize && y >= 0 && y < height as isize 
+                                for x in (grid.0 as isize - half_brush_size as isize)..(grid.0 as isize + half_brush_size as isize + 1) 
                                 {
-                                    let x = x as usize;
-                                    let y = y as usize;
-                                    pixels[y][x] = rgba_to_u8(rgba);
+                                    if x >= 0 && x < width as isize && y >= 0 && y < height as isize && selection::editable(selection, x as i32, y as i32)
+                                    {
+                                        let x = x as usize;
+                                        let y = y as usize;
+                                        pixels[y][x] = color::Color::from_array(rgba).to_u8();
+                                    }
                                 }
                             }
                         }
                     }
                 }
             }
+        }
+
+        for (pane, index, right) in split.panes(current, workspace)
+        {
+            let doc = match documents.get_mut(index)
+            {
+                Some(doc) => doc,
+                None => continue,
+            };
+            let (width, height) = (doc.width, doc.height);
+
+            let viewport = if right { &mut split.viewport } else { &mut doc.viewport };
+            if keep_on_screen
+            {
+                viewport.keep_visible((width as f32, height as f32), (pane.x, pane.y, pane.w, pane.h), 32.0);
+            }
+            let viewport = *viewport;
+
+            let p = viewport.canvas_to_screen((0.0, 0.0));
+            let draw_size = (width as f32 * viewport.scale, height as f32 * viewport.scale);
+            set_camera(&document::pane_camera(pane, layout.dpi_scale()));
 
             //canvas
-            draw_rectangle(p.0, p.1, draw_size.0, draw_size.1, Color::new(1.0, 1.0, 1.0, 0.25));
+            backdrop::draw_backdrop(checker_material, &backdrop, Rect::new(p.0, p.1, draw_size.0, draw_size.1));
             draw_rectangle_lines(p.0, p.1, draw_size.0, draw_size.1, 1.0, BLACK);
+            reference::draw_references(&mut doc.references, &viewport, false);
 
-            for i in 0..height
+            let canvas = &mut doc.canvas;
+            if canvas.width != width as usize || canvas.height != height as usize
             {
-                for j in 0..width
-                {
-                    let c = pixels[i as usize][j as usize];
-                    if c.3 > 0
-                    {
-                        draw_rectangle(p.0+j as f32 * grid_size, p.1+i as f32 * grid_size, grid_size, grid_size, Color::from_rgba(c.0, c.1, c.2, c.3));
-                    }
-                }
+                *canvas = Canvas::new(width as usize, height as usize);
             }
+            let cycle_map = if cycle_preview { doc.palette.cycle_map(get_time() as f32) } else { palette::CycleMap::new() };
+            canvas.upload(document::display_pixels(&doc.pixels, &doc.preview), view_filter, &cycle_map);
+            canvas.upload_selection(&doc.selection);
 
-            //grid
-            if show_grid
+            if side_by_side
             {
-                for i in 0..width
-                {
-                    draw_line(p.0+i as f32 * grid_size, p.1, p.0+i as f32 * grid_size, p.1+draw_size.1, 0.25, BLACK);
-                }
+                //unfiltered on the left, filtered copy on the right
+                canvas.draw(p.0, p.1, draw_size.0, draw_size.1, false);
+                canvas.draw_selection(p.0, p.1, draw_size.0, draw_size.1);
+
+                let p2 = (p.0 + draw_size.0 + viewport.scale, p.1);
+                backdrop::draw_backdrop(checker_material, &backdrop, Rect::new(p2.0, p2.1, draw_size.0, draw_size.1));
+                draw_rectangle_lines(p2.0, p2.1, draw_size.0, draw_size.1, 1.0, BLACK);
+                canvas.draw(p2.0, p2.1, draw_size.0, draw_size.1, true);
+            }
+            else
+            {
+                canvas.draw(p.0, p.1, draw_size.0, draw_size.1, true);
+                canvas.draw_selection(p.0, p.1, draw_size.0, draw_size.1);
+            }
 
-                for i in 0..height
-                {
-                    draw_line(p.0, p.1+i as f32 * grid_size, p.0+draw_size.0, p.1+i as f32 * grid_size, 0.25, BLACK);
-                }
+            reference::draw_references(&mut doc.references, &viewport, true);
+
+            //grid
+            grid::draw_grids(grid_material, &grid, Rect::new(p.0, p.1, draw_size.0, draw_size.1), (width as f32, height as f32), viewport.scale);
+
+            //rulers and guides, only for the main pane
+            if !right
+            {
+                rulers::draw_guides(&guides, &viewport, pane);
+                rulers::draw_rulers(&guides, &viewport, pane);
+            }
+            else
+            {
+                draw_line(pane.x, pane.y, pane.x, pane.y + pane.h, 2.0, BLACK);
             }
         }
+        document::reset_camera();
 
 
         egui_macroquad::draw();
@@ -411,39 +778,6 @@ async fn main()
     }
 }
 
-pub fn zoom_in(zoom: f32, minus_pos: (f32, f32)) -> (f32, (f32, f32))
-{
-    zoom_mul(2.0, zoom, minus_pos)
-}
-
-pub fn zoom_out(zoom: f32, minus_pos: (f32, f32)) -> (f32, (f32, f32))
-{
-    zoom_mul(0.5, zoom, minus_pos)
-}
-
-pub fn zoom_mul(val: f32, zoom: f32, minus_pos: (f32, f32)) -> (f32, (f32, f32))
-{
-    change_zoom(|zoom| zoom * val, zoom, minus_pos)
-}
-
-pub fn change_zoom<F: Fn(f32) -> f32>(op: F, zoom: f32, minus_pos: (f32, f32)) -> (f32, (f32, f32))
-{
-    let new_zoom = (op)(zoom).clamp(0.125, 1500.0);
-    let fac = new_zoom / zoom;
-    let pos = (minus_pos.0 * fac, minus_pos.1 * fac);
-    (new_zoom, pos)
-}
-
-fn to_grid(pos: (f32, f32), offset_x: f32, offset_y: f32, camera: (f32, f32), zoom: f32, grid_size: f32/*, width: f32, height: f32, grid_size: i32*/) -> (i32, i32)
-{
-    let canvas_x = offset_x - camera.0;
-    let canvas_y = offset_y - camera.1;
-
-    let canvas = ((pos.0 - canvas_x) as i32, (pos.1 - canvas_y) as i32);
-
-    (canvas.0/grid_size as i32, canvas.1/grid_size as i32)
-}
-
 fn two_to_one(two_d: Vec<Vec<(u8, u8, u8, u8)>>) -> Vec<(u8, u8, u8, u8)>
 {
     let mut result = Vec::new();
@@ -469,49 +803,13 @@ fn one_to_two(one_d: Vec<(u8, u8, u8, u8)>, rows: usize, cols: usize) -> Vec<Vec
     result
 }
 
-fn rgba_to_u8(rgba: [f32; 4]) -> (u8, u8, u8, u8)
-{
-    let one = (rgba[0]*255.0) as u8;
-    let two = (rgba[1]*255.0) as u8;
-    let three = (rgba[2]*255.0) as u8;
-    let four = (rgba[3]*255.0) as u8;
-    (one, two, three, four)
-}
-
-fn u8_to_rgba(u: (u8, u8, u8, u8)) -> [f32; 4]
-{
-    let one = u.0 as f32 / 255.0;
-    let two = u.1 as f32 / 255.0;
-    let three = u.2 as f32 / 255.0;
-    let four = u.3 as f32 / 255.0;
-    [one, two, three, four]
-}
-
-struct ByteBuffer
-{
-    /*
-    uint8_t* data;
-    uint32_t position;
-    uint32_t size;
-    uint32_t capacity;
-     */
-}
-
-struct Texture
-{
-    /*
-    uint32_t width;
-    uint32_t height;
-    uint32_t num_comps;
-    void* pixels;//Array of all pixels
-     */
-}
-
 pub struct Image
 {
     width: u16,
     height: u16,
-    pixels: Vec<(u8, u8, u8, u8)>
+    pixels: Vec<(u8, u8, u8, u8)>,
+    palette: Palette,
+    references: Vec<Reference>
 }
 
 impl Image
@@ -522,7 +820,9 @@ impl Image
         {
             width,
             height,
-            pixels
+            pixels,
+            palette: Palette::default(),
+            references: Vec::new()
         }
     }
 }
@@ -534,10 +834,51 @@ pub fn save(image: &Image, path: &str)
     file.write_all(&image.width.to_le_bytes()).unwrap();
     file.write_all(&image.height.to_le_bytes()).unwrap();
 
-    for (r, g, b, a) in image.pixels.to_vec()
+    for &(r, g, b, a) in image.pixels.iter()
     {
         file.write_all(&[r, g, b, a]).unwrap();
     }
+
+    //Optional chunks after the pixels: 4 byte tag, u32 length, data
+    write_chunk(&mut file, b"PALT", &image.palette.to_bytes()).unwrap();
+    if !image.references.is_empty()
+    {
+        write_chunk(&mut file, b"REFS", &reference::to_bytes(&image.references)).unwrap();
+    }
+}
+
+fn write_chunk(file: &mut File, tag: &[u8; 4], data: &[u8]) -> Result<(), std::io::Error>
+{
+    file.write_all(tag)?;
+    file.write_all(&(data.len() as u32).to_le_bytes())?;
+    file.write_all(data)
+}
+
+type Chunk = ([u8; 4], Vec<u8>);
+
+//None at the end of the file (older files have no chunks at all)
+fn read_chunk(file: &mut File) -> Result<Option<Chunk>, std::io::Error>
+{
+    let mut tag = [0; 4];
+    match file.read_exact(&mut tag)
+    {
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+        Ok(()) => {}
+    }
+
+    let mut len_bytes = [0; 4];
+    file.read_exact(&mut len_bytes)?;
+    //The length comes from the file, so it is only trusted as far as there are bytes to read
+    let len = u32::from_le_bytes(len_bytes) as u64;
+    let mut data = Vec::new();
+    Read::by_ref(file).take(len).read_to_end(&mut data)?;
+    if data.len() as u64 != len
+    {
+        return Err(std::io::Error::new(ErrorKind::UnexpectedEof, "chunk is cut off"));
+    }
+
+    Ok(Some((tag, data)))
 }
 
 pub fn load(path: &str) -> Result<Image, std::io::Error>
@@ -562,7 +903,19 @@ pub fn load(path: &str) -> Result<Image, std::io::Error>
         *pixel = (pixel_bytes[0], pixel_bytes[1], pixel_bytes[2], pixel_bytes[3]);
     }
 
-    let image = Image::new(width, height, pixels);
+    let mut image = Image::new(width, height, pixels);
+
+    while let Some((tag, data)) = read_chunk(&mut file)?
+    {
+        if &tag == b"PALT"
+        {
+            image.palette = Palette::from_bytes(&data).unwrap_or_default();
+        }
+        else if &tag == b"REFS"
+        {
+            image.references = reference::from_bytes(&data).unwrap_or_default();
+        }
+    }
 
     Ok(image)
 }
@@ -571,17 +924,91 @@ pub fn export_as_png(image: &Image, path: &str) -> Result<(), image::ImageError>
 {
     let p = path.to_string() + ".png";
     
-    let mut imgbuf = ImageBuffer::new(image.width.into(), image.height.into());
+    let imgbuf = to_image_buffer(image.width, image.height, &image.pixels);
+
+    let dynamic_image: DynamicImage = DynamicImage::ImageRgba8(imgbuf);
+
+    dynamic_image.save_with_format(p, image::ImageFormat::Png)
+}
+
+//Exports only the trimmed area, the offset into the full image goes into <path>.json for sprite sheet tools
+fn export_trimmed_png(doc: &Document, background: transform::TrimBackground, path: &str) -> Result<(), image::ImageError>
+{
+    let bounds = match transform::trim_bounds(&doc.pixels, background)
+    {
+        Some(bounds) => bounds,
+        None => return export_as_png(&doc.to_image(), path),
+    };
+    let (x0, y0, x1, y1) = bounds;
+
+    let pixels = two_to_one(transform::crop(&doc.pixels, bounds));
+    export_as_png(&Image::new((x1 - x0) as u16, (y1 - y0) as u16, pixels), path)?;
+
+    let json = format!("{{\n  \"x\": {},\n  \"y\": {},\n  \"width\": {},\n  \"height\": {},\n  \"source_width\": {},\n  \"source_height\": {}\n}}\n",
+        x0, y0, x1 - x0, y1 - y0, doc.width, doc.height);
+    std::fs::write(path.to_string() + ".json", json)?;
+
+    Ok(())
+}
+
+fn to_image_buffer(width: u16, height: u16, pixels: &[(u8, u8, u8, u8)]) -> RgbaImage
+{
+    let mut imgbuf = ImageBuffer::new(width.into(), height.into());
 
     for (x, y, pixel) in imgbuf.enumerate_pixels_mut()
     {
-        let (r, g, b, a) = image.pixels[x as usize + y as usize * image.width as usize];
+        let (r, g, b, a) = pixels[x as usize + y as usize * width as usize];
         *pixel = Rgba([r, g, b, a]);
     }
 
-    let dynamic_image: DynamicImage = DynamicImage::ImageRgba8(imgbuf);
+    imgbuf
+}
 
-    dynamic_image.save_with_format(p, image::ImageFormat::Png)
+//One frame per step of the fastest cycle, until all cycles loop
+fn cycle_frames(image: &Image) -> Vec<RgbaImage>
+{
+    let fps = image.palette.cycle_fps();
+    let mut frames = Vec::new();
+
+    for i in 0..image.palette.cycle_frames()
+    {
+        let map = image.palette.cycle_map(i as f32 / fps);
+        let pixels: Vec<(u8, u8, u8, u8)> = image.pixels.iter().map(|p| *map.get(p).unwrap_or(p)).collect();
+        frames.push(to_image_buffer(image.width, image.height, &pixels));
+    }
+
+    frames
+}
+
+pub fn export_cycle_gif(image: &Image, path: &str) -> Result<(), image::ImageError>
+{
+    let p = path.to_string() + ".gif";
+
+    let file = File::create(p)?;
+    let mut encoder = GifEncoder::new(file);
+    encoder.set_repeat(Repeat::Infinite)?;
+
+    let delay = cycle_frame_delay(image.palette.cycle_fps());
+    encoder.encode_frames(cycle_frames(image).into_iter().map(|buffer| Frame::from_parts(buffer, 0, 0, delay)))
+}
+
+//Time of one frame at the exact rate the frames were sampled at, the fps is scaled up so fractions aren't lost
+fn cycle_frame_delay(fps: f32) -> Delay
+{
+    const SCALE: f32 = 1000.0;
+    Delay::from_numer_denom_ms((1000.0 * SCALE) as u32, ((fps * SCALE).round() as u32).max(1))
+}
+
+//path_000.png, path_001.png, ...
+pub fn export_cycle_png_sequence(image: &Image, path: &str) -> Result<(), image::ImageError>
+{
+    for (i, buffer) in cycle_frames(image).into_iter().enumerate()
+    {
+        let p = format!("{}_{:03}.png", path, i);
+        DynamicImage::ImageRgba8(buffer).save_with_format(p, image::ImageFormat::Png)?;
+    }
+
+    Ok(())
 }
 
 pub fn import_from_png(path: &str) -> Result<Image, image::ImageError>
@@ -590,22 +1017,45 @@ pub fn import_from_png(path: &str) -> Result<Image, image::ImageError>
     
     let img = image::open(p)?;
 
-    if let DynamicImage::ImageRgba8(imgbuf) = img 
+    //Photos are often saved without alpha or with 16 bits per channel
+    let imgbuf = img.to_rgba8();
+    let (width, height) = imgbuf.dimensions();
+    if width > u16::MAX as u32 || height > u16::MAX as u32
     {
-        let (width, height) = imgbuf.dimensions();
-        let mut pixels = Vec::new();
+        return Err(image::ImageError::Unsupported(image::error::UnsupportedError::from_format_and_kind(ImageFormatHint::Unknown, image::error::UnsupportedErrorKind::Format(ImageFormatHint::Unknown))));
+    }
 
-        for pixel in imgbuf.pixels() 
-        {
-            let rgba = pixel.0;
-            pixels.push((rgba[0], rgba[1], rgba[2], rgba[3]));
-        }
+    let mut pixels = Vec::new();
+    for pixel in imgbuf.pixels() 
+    {
+        let rgba = pixel.0;
+        pixels.push((rgba[0], rgba[1], rgba[2], rgba[3]));
+    }
+
+    Ok(Image::new(width as u16, height as u16, pixels))
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
 
-        Ok(Image::new(width as u16, height as u16, pixels))
+    fn delay_ms(fps: f32) -> f32
+    {
+        let (numer, denom) = cycle_frame_delay(fps).numer_denom_ms();
+        numer as f32 / denom as f32
     }
-    else 
+
+    #[test]
+    fn cycle_delay_keeps_fractional_speeds()
     {
-        Err(image::ImageError::Unsupported(image::error::UnsupportedError::from_format_and_kind(ImageFormatHint::Unknown, image::error::UnsupportedErrorKind::Format(ImageFormatHint::Unknown))))
+        let mut palette = Palette { colors: vec![color::Color::from_u8((0, 0, 0, 255)); 4], ..Default::default() };
+        palette.cycles.push(palette::CycleRange { start: 0, end: 3, speed: 2.5, reverse: false });
+        assert_eq!(palette.cycle_fps(), 2.5);
+        assert!((delay_ms(palette.cycle_fps()) - 400.0).abs() < 0.01);
+        assert!((delay_ms(4.0) - 250.0).abs() < 0.01);
+        assert!((delay_ms(7.3) - 1000.0 / 7.3).abs() < 0.01);
+        assert!(delay_ms(0.4).is_finite());
     }
 }
 
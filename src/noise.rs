@@ -0,0 +1,363 @@
+use crate::color::Color;
+use crate::document::Document;
+use crate::rng::{self, Rng};
+use crate::selection::{self, Selection};
+
+type Pixels = Vec<Vec<(u8, u8, u8, u8)>>;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Generator
+{
+    ValueNoise,
+    Perlin,
+    Voronoi,
+    Dither,
+    Clusters,
+}
+
+impl Generator
+{
+    pub const ALL: [Generator; 5] = [Generator::ValueNoise, Generator::Perlin, Generator::Voronoi, Generator::Dither, Generator::Clusters];
+
+    pub fn name(self) -> &'static str
+    {
+        match self
+        {
+            Generator::ValueNoise => "Value Noise",
+            Generator::Perlin => "Perlin Noise",
+            Generator::Voronoi => "Voronoi Cells",
+            Generator::Dither => "Random Dither",
+            Generator::Clusters => "Clusters",
+        }
+    }
+}
+
+pub struct GeneratorSettings
+{
+    pub kind: Generator,
+    pub seed: u64,
+    pub scale: f32,//Size of a noise feature or Voronoi cell in pixels
+    pub octaves: u32,
+    pub density: f32,//Share of the first colour for dither, share of covered pixels for clusters
+    pub cluster_size: u32,
+    pub palette_start: usize,
+    pub palette_end: usize,
+}
+
+impl Default for GeneratorSettings
+{
+    fn default() -> Self
+    {
+        GeneratorSettings
+        {
+            kind: Generator::ValueNoise,
+            seed: 1,
+            scale: 8.0,
+            octaves: 3,
+            density: 0.5,
+            cluster_size: 6,
+            palette_start: 0,
+            palette_end: 3,
+        }
+    }
+}
+
+fn smoothstep(t: f32) -> f32
+{
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32
+{
+    a + (b - a) * t
+}
+
+//Random values on the corners of a grid, blended between them, 0.0..1.0
+pub fn value_noise(x: f32, y: f32, seed: u64) -> f32
+{
+    let (ix, iy) = (x.floor() as i32, y.floor() as i32);
+    let (tx, ty) = (smoothstep(x - ix as f32), smoothstep(y - iy as f32));
+    let corner = |dx: i32, dy: i32| rng::hash_f32(ix + dx, iy + dy, seed);
+
+    lerp(lerp(corner(0, 0), corner(1, 0), tx), lerp(corner(0, 1), corner(1, 1), tx), ty)
+}
+
+//Gradient noise, random directions on the grid corners, 0.0..1.0
+pub fn perlin_noise(x: f32, y: f32, seed: u64) -> f32
+{
+    let (ix, iy) = (x.floor() as i32, y.floor() as i32);
+    let (fx, fy) = (x - ix as f32, y - iy as f32);
+    let corner = |dx: i32, dy: i32|
+    {
+        let angle = rng::hash_f32(ix + dx, iy + dy, seed) * std::f32::consts::TAU;
+        angle.cos() * (fx - dx as f32) + angle.sin() * (fy - dy as f32)
+    };
+
+    let (tx, ty) = (smoothstep(fx), smoothstep(fy));
+    let value = lerp(lerp(corner(0, 0), corner(1, 0), tx), lerp(corner(0, 1), corner(1, 1), tx), ty);
+    //Unit gradients keep the value inside of -0.71..0.71
+    (value * std::f32::consts::FRAC_1_SQRT_2 + 0.5).clamp(0.0, 1.0)
+}
+
+//Octaves at double frequency and half strength, summed up and brought back to 0.0..1.0
+pub fn fractal(noise: fn(f32, f32, u64) -> f32, x: f32, y: f32, octaves: u32, seed: u64) -> f32
+{
+    let (mut sum, mut weight, mut total, mut frequency) = (0.0, 1.0, 0.0, 1.0);
+    for octave in 0..octaves.max(1)
+    {
+        sum += noise(x * frequency, y * frequency, seed.wrapping_add(octave as u64)) * weight;
+        total += weight;
+        weight *= 0.5;
+        frequency *= 2.0;
+    }
+    sum / total
+}
+
+//Every grid cell has one random point, pixels take the value of the closest point
+pub fn voronoi(x: f32, y: f32, seed: u64) -> f32
+{
+    let (ix, iy) = (x.floor() as i32, y.floor() as i32);
+    let mut closest = (f32::MAX, 0.0);
+
+    for cy in iy - 1..=iy + 1
+    {
+        for cx in ix - 1..=ix + 1
+        {
+            let h = rng::hash(cx, cy, seed);
+            let px = cx as f32 + (h & 0xFFFF) as f32 / 65536.0;
+            let py = cy as f32 + (h >> 16 & 0xFFFF) as f32 / 65536.0;
+            let distance = (px - x) * (px - x) + (py - y) * (py - y);
+            if distance < closest.0
+            {
+                closest = (distance, (h >> 40) as f32 / (1u64 << 24) as f32);
+            }
+        }
+    }
+
+    closest.1
+}
+
+fn pick(colors: &[Color], value: f32) -> (u8, u8, u8, u8)
+{
+    colors[((value * colors.len() as f32) as usize).min(colors.len() - 1)].to_u8()
+}
+
+//Fills the selection, or everything without one, the result only depends on the settings and the size
+pub fn generate(pixels: &Pixels, selection: &Option<Selection>, settings: &GeneratorSettings, colors: &[Color]) -> Pixels
+{
+    let mut result = pixels.clone();
+    if colors.is_empty()
+    {
+        return result;
+    }
+
+    let scale = settings.scale.max(1.0);
+    let seed = settings.seed;
+    let mut rng = Rng::new(seed);
+    let height = result.len();
+    let width = if height > 0 { result[0].len() } else { 0 };
+
+    if settings.kind == Generator::Clusters
+    {
+        //Random walks from random editable pixels until enough pixels are covered
+        let editable: Vec<(i32, i32)> = (0..height as i32).flat_map(|y| (0..width as i32).map(move |x| (x, y))).filter(|(x, y)| selection::editable(selection, *x, *y)).collect();
+        if editable.is_empty()
+        {
+            return result;
+        }
+        let count = (editable.len() as f32 * settings.density / settings.cluster_size.max(1) as f32).round() as usize;
+        for _ in 0..count
+        {
+            let color = colors[rng.below(colors.len())].to_u8();
+            let (mut x, mut y) = editable[rng.below(editable.len())];
+            for _ in 0..settings.cluster_size.max(1)
+            {
+                if x >= 0 && y >= 0 && (x as usize) < width && (y as usize) < height && selection::editable(selection, x, y)
+                {
+                    result[y as usize][x as usize] = color;
+                }
+                match rng.below(4)
+                {
+                    0 => x += 1,
+                    1 => x -= 1,
+                    2 => y += 1,
+                    _ => y -= 1,
+                }
+            }
+        }
+        return result;
+    }
+
+    for (y, row) in result.iter_mut().enumerate()
+    {
+        for (x, pixel) in row.iter_mut().enumerate()
+        {
+            //Dither takes a number for every pixel, so the pattern stays the same with and without a selection
+            let chance = rng.next_f32();
+            if !selection::editable(selection, x as i32, y as i32)
+            {
+                continue;
+            }
+
+            let (fx, fy) = (x as f32 / scale, y as f32 / scale);
+            *pixel = match settings.kind
+            {
+                Generator::ValueNoise => pick(colors, fractal(value_noise, fx, fy, settings.octaves, seed)),
+                Generator::Perlin => pick(colors, fractal(perlin_noise, fx, fy, settings.octaves, seed)),
+                Generator::Voronoi => pick(colors, voronoi(fx, fy, seed)),
+                Generator::Dither => if chance < settings.density { colors[0].to_u8() } else { colors[1.min(colors.len() - 1)].to_u8() },
+                Generator::Clusters => *pixel,
+            };
+        }
+    }
+
+    result
+}
+
+pub fn generate_ui(ui: &mut egui::Ui, settings: &mut GeneratorSettings, doc: &mut Document, rgba: [f32; 4])
+{
+    ui.collapsing("Generate", |ui|
+    {
+        egui::ComboBox::from_id_source("generator").selected_text(settings.kind.name()).show_ui(ui, |ui|
+        {
+            for kind in Generator::ALL
+            {
+                ui.selectable_value(&mut settings.kind, kind, kind.name());
+            }
+        });
+
+        ui.horizontal(|ui|
+        {
+            ui.add(egui::DragValue::new(&mut settings.seed).clamp_range(0..=rng::MAX_SEED).prefix("seed "));
+            if ui.button("New Seed").clicked()
+            {
+                settings.seed = Rng::new(settings.seed).next_u64() >> 11;
+            }
+        });
+        match settings.kind
+        {
+            Generator::ValueNoise | Generator::Perlin =>
+            {
+                ui.add(egui::Slider::new(&mut settings.scale, 1.0..=64.0).text("Scale"));
+                ui.add(egui::Slider::new(&mut settings.octaves, 1..=6).text("Octaves"));
+            }
+            Generator::Voronoi =>
+            {
+                ui.add(egui::Slider::new(&mut settings.scale, 1.0..=64.0).text("Cell size"));
+            }
+            Generator::Dither =>
+            {
+                ui.add(egui::Slider::new(&mut settings.density, 0.0..=1.0).text("First color"));
+            }
+            Generator::Clusters =>
+            {
+                ui.add(egui::Slider::new(&mut settings.density, 0.0..=1.0).text("Density"));
+                ui.add(egui::Slider::new(&mut settings.cluster_size, 1..=64).text("Cluster size"));
+            }
+        }
+
+        //Palette colours start..=end, or black and the current colour without a palette
+        let colors: Vec<Color> = if doc.palette.colors.is_empty()
+        {
+            ui.label("Uses black and the current color");
+            vec![Color::new(0.0, 0.0, 0.0, 1.0), Color::from_array(rgba)]
+        }
+        else
+        {
+            let last = doc.palette.colors.len() - 1;
+            ui.horizontal(|ui|
+            {
+                ui.label("Palette");
+                ui.add(egui::DragValue::new(&mut settings.palette_start).clamp_range(0..=last));
+                ui.add(egui::DragValue::new(&mut settings.palette_end).clamp_range(0..=last));
+            });
+            let (start, end) = (settings.palette_start.min(last), settings.palette_end.min(last));
+            doc.palette.colors[start.min(end)..=end.max(start)].to_vec()
+        };
+
+        if ui.button("Generate").clicked()
+        {
+            doc.checkpoint();
+            doc.pixels = generate(&doc.pixels, &doc.selection, settings, &colors);
+            doc.canvas.mark_all_dirty();
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    const COLORS: [Color; 3] = [Color { r: 1.0, g: 0.0, b: 0.0, a: 1.0 }, Color { r: 0.0, g: 1.0, b: 0.0, a: 1.0 }, Color { r: 0.0, g: 0.0, b: 1.0, a: 1.0 }];
+    const BLANK: (u8, u8, u8, u8) = (0, 0, 0, 0);
+
+    fn blank(width: usize, height: usize) -> Pixels
+    {
+        vec![vec![BLANK; width]; height]
+    }
+
+    fn settings(kind: Generator, seed: u64) -> GeneratorSettings
+    {
+        GeneratorSettings { kind, seed, palette_end: 2, ..Default::default() }
+    }
+
+    #[test]
+    fn same_seed_same_pixels()
+    {
+        for kind in Generator::ALL
+        {
+            let a = generate(&blank(32, 24), &None, &settings(kind, 7), &COLORS);
+            let b = generate(&blank(32, 24), &None, &settings(kind, 7), &COLORS);
+            assert!(a == b, "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn different_seeds_differ()
+    {
+        for kind in Generator::ALL
+        {
+            let a = generate(&blank(32, 24), &None, &settings(kind, 7), &COLORS);
+            let b = generate(&blank(32, 24), &None, &settings(kind, 8), &COLORS);
+            assert!(a != b, "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn pixels_outside_selection_stay()
+    {
+        let (width, height) = (32, 24);
+        let mut selection = Selection::new(width, height);
+        for y in 20..23
+        {
+            for x in 28..31
+            {
+                selection.mask[y * width + x] = true;
+            }
+        }
+        let selection = Some(selection);
+
+        for kind in Generator::ALL
+        {
+            let result = generate(&blank(width, height), &selection, &settings(kind, 3), &COLORS);
+            let mut changed = 0;
+            for (y, row) in result.iter().enumerate()
+            {
+                for (x, pixel) in row.iter().enumerate()
+                {
+                    if selection::editable(&selection, x as i32, y as i32)
+                    {
+                        changed += (*pixel != BLANK) as usize;
+                    }
+                    else
+                    {
+                        assert_eq!(*pixel, BLANK, "{:?} at {}, {}", kind, x, y);
+                    }
+                }
+            }
+            //Clusters start inside of the small selection, so some of it gets covered
+            assert!(changed > 0, "{:?}", kind);
+        }
+    }
+}
@@ -0,0 +1,620 @@
+use std::collections::HashMap;
+
+use crate::color::Color;
+use crate::layout;
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum ColorModel
+{
+    Hsv,
+    Hsl,
+    Oklch,
+}
+
+impl ColorModel
+{
+    fn values_of(self, rgba: [f32; 4]) -> [f32; 4]
+    {
+        let color = Color::from_array(rgba);
+        match self
+        {
+            ColorModel::Hsv => color.to_hsv(),
+            ColorModel::Hsl => color.to_hsl(),
+            ColorModel::Oklch => color.to_oklch(),
+        }
+    }
+
+    fn rgba_of(self, values: [f32; 4]) -> [f32; 4]
+    {
+        match self
+        {
+            ColorModel::Hsv => Color::from_hsv(values),
+            ColorModel::Hsl => Color::from_hsl(values),
+            ColorModel::Oklch => Color::from_oklch(values),
+        }.to_array()
+    }
+}
+
+//Slider values of the colour model, kept between frames so hue and saturation
+//don't jump back to 0 on greys, black and white where rgba can't tell what they were
+pub struct ColorSliders
+{
+    pub model: ColorModel,
+    values: [f32; 4],
+    rgba: [f32; 4],//Colour the values were last synced with
+}
+
+impl Default for ColorSliders
+{
+    fn default() -> Self
+    {
+        ColorSliders
+        {
+            model: ColorModel::Hsv,
+            values: ColorModel::Hsv.values_of([0.0, 0.0, 0.0, 1.0]),
+            rgba: [0.0, 0.0, 0.0, 1.0],
+        }
+    }
+}
+
+impl ColorSliders
+{
+    //Only takes the colour over when it was changed somewhere else, values it doesn't define are kept
+    fn sync(&mut self, rgba: [f32; 4])
+    {
+        if rgba == self.rgba
+        {
+            return;
+        }
+
+        let old = self.values;
+        self.values = self.model.values_of(rgba);
+
+        //Without saturation or chroma the hue is open, black (and white in HSL) leaves the saturation open too
+        let hue = if self.model == ColorModel::Oklch { 2 } else { 0 };
+        if self.values[1] <= 1e-4
+        {
+            self.values[hue] = old[hue];
+        }
+        let open_saturation = match self.model
+        {
+            ColorModel::Hsv => self.values[2] <= 1e-4,
+            ColorModel::Hsl => self.values[2] <= 1e-4 || self.values[2] >= 1.0 - 1e-4,
+            ColorModel::Oklch => false,
+        };
+        if open_saturation
+        {
+            self.values[1] = old[1];
+        }
+        self.rgba = rgba;
+    }
+
+    fn set_model(&mut self, model: ColorModel)
+    {
+        if model != self.model
+        {
+            self.model = model;
+            self.values = model.values_of(self.rgba);
+        }
+    }
+}
+
+//Longest animation cycle_frames gives for exports
+const MAX_CYCLE_FRAMES: usize = 256;
+
+//Original pixel colour -> colour it is drawn as
+pub type CycleMap = HashMap<(u8, u8, u8, u8), (u8, u8, u8, u8)>;
+
+//Part of the document, saved together with the pixels
+#[derive(Clone, Default)]
+pub struct Palette
+{
+    pub colors: Vec<Color>,
+    pub cycles: Vec<CycleRange>,
+}
+
+//Colours start..=end rotate through each other, speed is in steps per second
+#[derive(Clone, Copy)]
+pub struct CycleRange
+{
+    pub start: usize,
+    pub end: usize,
+    pub speed: f32,
+    pub reverse: bool,
+}
+
+impl CycleRange
+{
+    pub fn len(&self) -> usize
+    {
+        self.end + 1 - self.start
+    }
+
+    //How many steps the range has moved at this time
+    pub fn offset(&self, time: f32) -> usize
+    {
+        let steps = (time * self.speed).floor() as i64;
+        let steps = if self.reverse { -steps } else { steps };
+        steps.rem_euclid(self.len() as i64) as usize
+    }
+}
+
+impl Palette
+{
+    //Closest palette colour by OKLab distance, alpha is kept from the input
+    pub fn nearest(&self, color: Color) -> Option<Color>
+    {
+        let opaque = Color { a: 1.0, ..color };
+        self.colors.iter()
+            .min_by(|a, b| Color { a: 1.0, ..**a }.distance_oklab(opaque).total_cmp(&Color { a: 1.0, ..**b }.distance_oklab(opaque)))
+            .map(|c| Color { a: color.a, ..*c })
+    }
+
+    //Which colour every cycling colour gets drawn as at this time
+    pub fn cycle_map(&self, time: f32) -> CycleMap
+    {
+        let mut map = HashMap::new();
+
+        for cycle in self.valid_cycles()
+        {
+            let offset = cycle.offset(time);
+            for i in 0..cycle.len()
+            {
+                let from = self.colors[cycle.start + i].to_u8();
+                let to = self.colors[cycle.start + (i + offset) % cycle.len()].to_u8();
+                map.entry(from).or_insert(to);
+            }
+        }
+
+        map
+    }
+
+    //Cycle ranges keep pointing at the same colours, ranges left without colours are dropped
+    pub fn remove_color(&mut self, index: usize)
+    {
+        if index >= self.colors.len()
+        {
+            return;
+        }
+        self.colors.remove(index);
+
+        self.cycles.retain_mut(|cycle|
+        {
+            if cycle.start > index
+            {
+                cycle.start -= 1;
+                cycle.end = cycle.end.saturating_sub(1);//Broken ranges from a file can end before they start
+            }
+            else if cycle.end >= index
+            {
+                if cycle.start == cycle.end
+                {
+                    return false;
+                }
+                cycle.end -= 1;
+            }
+            true
+        });
+    }
+
+    //Ranges that fit into the palette, others are ignored everywhere
+    fn valid_cycles(&self) -> impl Iterator<Item = &CycleRange>
+    {
+        self.cycles.iter().filter(|c| c.end < self.colors.len() && c.start <= c.end)
+    }
+
+    //Frames at cycle_fps until every range is back at the start, at most 256
+    pub fn cycle_frames(&self) -> usize
+    {
+        let fps = self.cycle_fps();
+        let moving: Vec<&CycleRange> = self.valid_cycles().filter(|c| c.speed > 0.0 && c.speed.is_finite()).collect();
+        let length = moving.iter().fold(1, |frames, c| lcm(frames, c.len()));
+
+        //A range is back at the start once the steps it took are a multiple of its length
+        (1..=MAX_CYCLE_FRAMES).find(|frames|
+        {
+            moving.iter().all(|c|
+            {
+                let loops = *frames as f32 * c.speed / fps / c.len() as f32;
+                (loops - loops.round()).abs() < 1e-3 && loops.round() >= 1.0
+            })
+        })
+        .unwrap_or(length.min(MAX_CYCLE_FRAMES))
+    }
+
+    //Fastest range moves one step per frame
+    pub fn cycle_fps(&self) -> f32
+    {
+        self.valid_cycles().map(|c| c.speed).filter(|s| s.is_finite()).fold(0.0, f32::max).max(1.0)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8>
+    {
+        let mut bytes = Vec::new();
+
+        bytes.extend((self.colors.len() as u16).to_le_bytes());
+        for color in self.colors.iter()
+        {
+            let (r, g, b, a) = color.to_u8();
+            bytes.extend([r, g, b, a]);
+        }
+
+        bytes.extend((self.cycles.len() as u16).to_le_bytes());
+        for cycle in self.cycles.iter()
+        {
+            bytes.extend((cycle.start as u16).to_le_bytes());
+            bytes.extend((cycle.end as u16).to_le_bytes());
+            bytes.extend(cycle.speed.to_le_bytes());
+            bytes.push(cycle.reverse as u8);
+        }
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<Palette>
+    {
+        let mut palette = Palette::default();
+        let mut pos = 0;
+
+        let count = u16::from_le_bytes(bytes.get(pos..pos+2)?.try_into().ok()?);
+        pos += 2;
+        for _ in 0..count
+        {
+            let c = bytes.get(pos..pos+4)?;
+            palette.colors.push(Color::from_u8((c[0], c[1], c[2], c[3])));
+            pos += 4;
+        }
+
+        let count = u16::from_le_bytes(bytes.get(pos..pos+2)?.try_into().ok()?);
+        pos += 2;
+        for _ in 0..count
+        {
+            let c = bytes.get(pos..pos+9)?;
+            palette.cycles.push(CycleRange
+            {
+                start: u16::from_le_bytes([c[0], c[1]]) as usize,
+                end: u16::from_le_bytes([c[2], c[3]]) as usize,
+                speed: f32::from_le_bytes([c[4], c[5], c[6], c[7]]),
+                reverse: c[8] != 0,
+            });
+            pos += 9;
+        }
+
+        Some(palette)
+    }
+}
+
+fn lcm(a: usize, b: usize) -> usize
+{
+    let mut x = a;
+    let mut y = b;
+    while y != 0
+    {
+        let t = x % y;
+        x = y;
+        y = t;
+    }
+    a / x * b
+}
+
+pub struct RampSettings
+{
+    pub steps: usize,
+    pub lightness_min: f32,
+    pub lightness_max: f32,
+    pub hue_shift: f32,//Degrees between the darkest and the lightest colour
+    pub hue_curve: f32,
+    pub saturation_falloff: f32,//How much chroma is lost at both ends of the ramp
+    pub saturation_curve: f32,
+}
+
+impl Default for RampSettings
+{
+    fn default() -> Self
+    {
+        RampSettings
+        {
+            steps: 5,
+            lightness_min: 0.25,
+            lightness_max: 0.9,
+            hue_shift: 40.0,
+            hue_curve: 1.0,
+            saturation_falloff: 0.4,
+            saturation_curve: 2.0,
+        }
+    }
+}
+
+//Goes from dark to light in OKLCH, so every step has the same perceived lightness difference
+//Shadows get shifted one way on the hue wheel and highlights the other (negative hue_shift flips it)
+pub fn generate_ramp(base: Color, settings: &RampSettings) -> Vec<Color>
+{
+    let lch = base.to_oklch();
+    let mut ramp = Vec::new();
+
+    for i in 0..settings.steps
+    {
+        let t = if settings.steps > 1 { i as f32 / (settings.steps - 1) as f32 } else { 0.5 };
+        let centered = t * 2.0 - 1.0;//-1.0 darkest, 1.0 lightest
+
+        let l = settings.lightness_min + (settings.lightness_max - settings.lightness_min) * t;
+        let hue_offset = settings.hue_shift / 2.0 * centered.signum() * centered.abs().powf(settings.hue_curve);
+        let chroma = lch[1] * (1.0 - settings.saturation_falloff * centered.abs().powf(settings.saturation_curve));
+
+        ramp.push(Color::from_oklch([l, chroma.max(0.0), lch[2] + hue_offset, base.a]));
+    }
+
+    ramp
+}
+
+pub fn palette_window(egui_ctx: &egui::Context, panel: Option<&mut egui::Ui>, palette: &mut Palette, rgba: &mut [f32; 4], ramp: &mut RampSettings, cycle_preview: &mut bool)
+{
+    layout::section(egui_ctx, panel, "Palette", (egui::Align2::RIGHT_CENTER, egui::vec2(-50.0, -150.0)), |ui|
+    {
+        let mut remove = None;
+        ui.horizontal_wrapped(|ui|
+        {
+            for (i, color) in palette.colors.iter().enumerate()
+            {
+                let response = swatch(ui, *color, 18.0).on_hover_text(format!("{}\nLeft click: select, Right click: remove", i));
+                if response.clicked()
+                {
+                    *rgba = color.to_array();
+                }
+                if response.secondary_clicked()
+                {
+                    remove = Some(i);
+                }
+            }
+        });
+        if let Some(i) = remove
+        {
+            palette.remove_color(i);
+        }
+        ui.spacing();
+        if ui.button("Add Color").clicked()
+        {
+            palette.colors.push(Color::from_array(*rgba));
+        }
+        ui.separator();
+
+        ui.label("Ramp (from current color)");
+        ui.add(egui::Slider::new(&mut ramp.steps, 2..=16).text("Steps"));
+        ui.add(egui::Slider::new(&mut ramp.lightness_min, 0.0..=1.0).text("Darkest"));
+        ui.add(egui::Slider::new(&mut ramp.lightness_max, 0.0..=1.0).text("Lightest"));
+        ui.add(egui::Slider::new(&mut ramp.hue_shift, -180.0..=180.0).text("Hue Shift"));
+        ui.add(egui::Slider::new(&mut ramp.hue_curve, 0.25..=4.0).text("Hue Curve"));
+        ui.add(egui::Slider::new(&mut ramp.saturation_falloff, 0.0..=1.0).text("Saturation Falloff"));
+        ui.add(egui::Slider::new(&mut ramp.saturation_curve, 0.25..=4.0).text("Saturation Curve"));
+
+        let generated = generate_ramp(Color::from_array(*rgba), ramp);
+        ui.horizontal(|ui|
+        {
+            for color in generated.iter()
+            {
+                swatch(ui, *color, 18.0);
+            }
+        });
+        if ui.button("Insert Ramp").clicked()
+        {
+            palette.colors.extend(generated);
+        }
+        ui.separator();
+
+        ui.label("Color Cycling");
+        ui.checkbox(cycle_preview, "Animate on canvas");
+        let last = palette.colors.len().saturating_sub(1);
+        let mut remove = None;
+        for (i, cycle) in palette.cycles.iter_mut().enumerate()
+        {
+            ui.horizontal(|ui|
+            {
+                ui.add(egui::DragValue::new(&mut cycle.start).clamp_range(0..=last).prefix("from "));
+                ui.add(egui::DragValue::new(&mut cycle.end).clamp_range(cycle.start..=last.max(cycle.start)).prefix("to "));
+                ui.add(egui::DragValue::new(&mut cycle.speed).clamp_range(0.1..=60.0).speed(0.1).suffix(" /s"));
+                ui.checkbox(&mut cycle.reverse, "Reverse");
+                if ui.button("X").clicked()
+                {
+                    remove = Some(i);
+                }
+            });
+        }
+        if let Some(i) = remove
+        {
+            palette.cycles.remove(i);
+        }
+        if ui.button("Add Cycle").clicked() && palette.colors.len() > 1
+        {
+            palette.cycles.push(CycleRange { start: 0, end: last, speed: 4.0, reverse: false });
+        }
+    });
+}
+
+//HSV/HSL/OKLCH sliders that edit the same rgba value as the color editor
+pub fn color_model_sliders(ui: &mut egui::Ui, rgba: &mut [f32; 4], sliders: &mut ColorSliders)
+{
+    sliders.sync(*rgba);
+    let mut model = sliders.model;
+    ui.horizontal(|ui|
+    {
+        ui.selectable_value(&mut model, ColorModel::Hsv, "HSV");
+        ui.selectable_value(&mut model, ColorModel::Hsl, "HSL");
+        ui.selectable_value(&mut model, ColorModel::Oklch, "OKLCH");
+    });
+    sliders.set_model(model);
+
+    let values = &mut sliders.values;
+    let mut changed = false;
+    match model
+    {
+        ColorModel::Hsv =>
+        {
+            changed |= ui.add(egui::Slider::new(&mut values[0], 0.0..=360.0).text("H")).changed();
+            changed |= ui.add(egui::Slider::new(&mut values[1], 0.0..=1.0).text("S")).changed();
+            changed |= ui.add(egui::Slider::new(&mut values[2], 0.0..=1.0).text("V")).changed();
+        }
+        ColorModel::Hsl =>
+        {
+            changed |= ui.add(egui::Slider::new(&mut values[0], 0.0..=360.0).text("H")).changed();
+            changed |= ui.add(egui::Slider::new(&mut values[1], 0.0..=1.0).text("S")).changed();
+            changed |= ui.add(egui::Slider::new(&mut values[2], 0.0..=1.0).text("L")).changed();
+        }
+        ColorModel::Oklch =>
+        {
+            changed |= ui.add(egui::Slider::new(&mut values[0], 0.0..=1.0).text("L")).changed();
+            changed |= ui.add(egui::Slider::new(&mut values[1], 0.0..=0.37).text("C")).changed();
+            changed |= ui.add(egui::Slider::new(&mut values[2], 0.0..=360.0).text("H")).changed();
+        }
+    }
+
+    if changed
+    {
+        values[3] = rgba[3];
+        *rgba = model.rgba_of(*values);
+        sliders.rgba = *rgba;
+    }
+}
+
+pub fn swatch(ui: &mut egui::Ui, color: Color, size: f32) -> egui::Response
+{
+    let (rect, response) = ui.allocate_exact_size(egui::vec2(size, size), egui::Sense::click());
+    ui.painter().rect_filled(rect, 2.0, color.to_egui());
+    ui.painter().rect_stroke(rect, 2.0, egui::Stroke::new(1.0, egui::Color32::BLACK));
+    response
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn with_cycles(size: usize, cycles: &[(usize, usize, f32)]) -> Palette
+    {
+        Palette
+        {
+            colors: (0..size).map(|i| Color { r: i as f32 / size as f32, g: 0.0, b: 0.0, a: 1.0 }).collect(),
+            cycles: cycles.iter().map(|(start, end, speed)| CycleRange { start: *start, end: *end, speed: *speed, reverse: false }).collect(),
+        }
+    }
+
+    //The map at the frame after the last one has to be the one of the first frame again
+    fn loops_cleanly(palette: &Palette)
+    {
+        let (frames, fps) = (palette.cycle_frames(), palette.cycle_fps());
+        for i in 0..frames
+        {
+            assert!(palette.cycle_map((i + frames) as f32 / fps) == palette.cycle_map(i as f32 / fps), "frame {} of {}", i, frames);
+        }
+    }
+
+    #[test]
+    fn hue_survives_greys()
+    {
+        for model in [ColorModel::Hsv, ColorModel::Hsl]
+        {
+            let mut sliders = ColorSliders { model, ..Default::default() };
+            sliders.values = [200.0, 0.8, 0.0, 1.0];
+            //Value or lightness 0 is black, hue and saturation have to stay where they were
+            sliders.rgba = model.rgba_of(sliders.values);
+            sliders.sync(sliders.rgba);
+            assert_eq!(sliders.values[..2], [200.0, 0.8]);
+
+            //Saturation 0 from another tool keeps the hue
+            sliders.sync([0.5, 0.5, 0.5, 1.0]);
+            assert_eq!(sliders.values[0], 200.0);
+            assert!(sliders.values[1] <= 1e-4);
+
+            //A colour with a hue replaces it
+            sliders.sync([1.0, 0.0, 0.0, 1.0]);
+            assert!(sliders.values[0].abs() < 0.5 || (sliders.values[0] - 360.0).abs() < 0.5, "{:?}", model);
+        }
+    }
+
+    #[test]
+    fn oklch_hue_survives_greys()
+    {
+        let mut sliders = ColorSliders::default();
+        sliders.set_model(ColorModel::Oklch);
+        sliders.values[2] = 120.0;
+        sliders.sync([0.2, 0.2, 0.2, 1.0]);
+        assert_eq!(sliders.values[2], 120.0);
+    }
+
+    #[test]
+    fn same_rgba_keeps_values()
+    {
+        let mut sliders = ColorSliders::default();
+        sliders.values = [90.0, 0.0, 0.3, 1.0];
+        sliders.rgba = ColorModel::Hsv.rgba_of(sliders.values);
+        let values = sliders.values;
+        sliders.sync(sliders.rgba);
+        assert_eq!(sliders.values, values);
+    }
+
+    #[test]
+    fn removing_colors_moves_cycles()
+    {
+        let mut palette = with_cycles(10, &[(0, 2, 1.0), (3, 5, 1.0), (6, 6, 1.0), (7, 9, 1.0)]);
+        let sixth = palette.colors[6].to_u8();
+        palette.remove_color(4);
+        let ranges: Vec<(usize, usize)> = palette.cycles.iter().map(|c| (c.start, c.end)).collect();
+        assert_eq!(ranges, vec![(0, 2), (3, 4), (5, 5), (6, 8)]);
+        assert_eq!(palette.colors[5].to_u8(), sixth);
+
+        //A range of one colour disappears with it
+        palette.remove_color(5);
+        let ranges: Vec<(usize, usize)> = palette.cycles.iter().map(|c| (c.start, c.end)).collect();
+        assert_eq!(ranges, vec![(0, 2), (3, 4), (5, 7)]);
+
+        //Removing the start of a range keeps the rest of it
+        palette.remove_color(0);
+        let ranges: Vec<(usize, usize)> = palette.cycles.iter().map(|c| (c.start, c.end)).collect();
+        assert_eq!(ranges, vec![(0, 1), (2, 3), (4, 6)]);
+        assert_eq!(palette.colors.len(), 7);
+
+        palette.remove_color(100);
+        assert_eq!(palette.colors.len(), 7);
+    }
+
+    #[test]
+    fn same_speeds_use_lcm_of_lengths()
+    {
+        let palette = with_cycles(16, &[(0, 3, 4.0), (4, 9, 4.0)]);
+        assert_eq!(palette.cycle_frames(), 12);
+        loops_cleanly(&palette);
+    }
+
+    #[test]
+    fn slower_ranges_take_more_frames()
+    {
+        //The second range moves one step every other frame, so it needs 8 frames for its 4 colours
+        let palette = with_cycles(16, &[(0, 2, 8.0), (4, 7, 4.0)]);
+        assert_eq!(palette.cycle_fps(), 8.0);
+        assert_eq!(palette.cycle_frames(), 24);
+        loops_cleanly(&palette);
+
+        let palette = with_cycles(16, &[(0, 3, 3.0), (4, 7, 2.0)]);
+        assert_eq!(palette.cycle_frames(), 12);
+        loops_cleanly(&palette);
+    }
+
+    #[test]
+    fn invalid_ranges_are_ignored()
+    {
+        let valid = with_cycles(8, &[(0, 3, 4.0)]);
+        let mut with_invalid = valid.clone();
+        with_invalid.cycles.push(CycleRange { start: 5, end: 20, speed: 60.0, reverse: false });
+        with_invalid.cycles.push(CycleRange { start: 6, end: 2, speed: 10.0, reverse: false });
+        assert_eq!(with_invalid.cycle_fps(), valid.cycle_fps());
+        assert_eq!(with_invalid.cycle_frames(), valid.cycle_frames());
+        loops_cleanly(&with_invalid);
+    }
+
+    #[test]
+    fn frames_are_capped()
+    {
+        let palette = with_cycles(64, &[(0, 30, 7.0), (31, 60, 3.0)]);
+        assert_eq!(palette.cycle_frames(), MAX_CYCLE_FRAMES);
+        assert_eq!(with_cycles(4, &[]).cycle_frames(), 1);
+    }
+}
@@ -0,0 +1,551 @@
+use macroquad::prelude::get_time;
+
+use crate::color::Color;
+use crate::document::Document;
+use crate::palette::Palette;
+use crate::Image;
+
+type Pixels = Vec<Vec<(u8, u8, u8, u8)>>;
+
+const TRANSPARENT: (u8, u8, u8, u8) = (0, 0, 0, 0);
+const PREVIEW_SIZE: f32 = 256.0;
+const REBUILD_DELAY: f64 = 0.2;//Seconds the parameters have to stay the same before the preview is made again
+
+const BAYER: [[f32; 4]; 4] =
+[
+    [0.0, 8.0, 2.0, 10.0],
+    [12.0, 4.0, 14.0, 6.0],
+    [3.0, 11.0, 1.0, 9.0],
+    [15.0, 7.0, 13.0, 5.0],
+];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Downscale
+{
+    Box,
+    Median,
+    EdgePreserving,//Averages only the larger of the dark and light half of each block, so edges don't get blurred
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Dither
+{
+    None,
+    Ordered,
+    FloydSteinberg,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct PixelateParams
+{
+    pub width: i32,
+    pub height: i32,
+    pub method: Downscale,
+    pub outline: bool,
+    pub outline_threshold: f32,//OKLab lightness difference that counts as an edge
+    pub outline_color: [f32; 4],
+    pub colors: usize,
+    pub use_palette: bool,//Quantise to the palette of the current document instead of finding colours
+    pub dither: Dither,
+    pub dither_strength: f32,
+}
+
+impl Default for PixelateParams
+{
+    fn default() -> Self
+    {
+        PixelateParams
+        {
+            width: 64,
+            height: 64,
+            method: Downscale::EdgePreserving,
+            outline: false,
+            outline_threshold: 0.2,
+            outline_color: [0.0, 0.0, 0.0, 1.0],
+            colors: 16,
+            use_palette: false,
+            dither: Dither::None,
+            dither_strength: 0.5,
+        }
+    }
+}
+
+//Block of source pixels (x0, y0, x1, y1) that becomes the target pixel at x, y
+fn block(x: usize, y: usize, source: (usize, usize), target: (usize, usize)) -> (usize, usize, usize, usize)
+{
+    let x0 = x * source.0 / target.0;
+    let y0 = y * source.1 / target.1;
+    let x1 = ((x + 1) * source.0 / target.0).max(x0 + 1).min(source.0);
+    let y1 = ((y + 1) * source.1 / target.1).max(y0 + 1).min(source.1);
+    (x0, y0, x1, y1)
+}
+
+fn average(colors: &[Color]) -> Color
+{
+    let mut sum = [0.0; 4];
+    for c in colors
+    {
+        let p = c.premultiplied();
+        for i in 0..4
+        {
+            sum[i] += p[i];
+        }
+    }
+    let n = colors.len().max(1) as f32;
+    Color::from_premultiplied([sum[0] / n, sum[1] / n, sum[2] / n, sum[3] / n])
+}
+
+fn median(colors: &mut [Color]) -> Color
+{
+    let mut channel = |f: fn(&Color) -> f32|
+    {
+        colors.sort_by(|a, b| f(a).total_cmp(&f(b)));
+        f(&colors[colors.len() / 2])
+    };
+    Color::new(channel(|c| c.r), channel(|c| c.g), channel(|c| c.b), 1.0)
+}
+
+fn edge_preserving(colors: &[Color]) -> Color
+{
+    let lightness: Vec<f32> = colors.iter().map(|c| c.to_oklab()[0]).collect();
+    let mean = lightness.iter().sum::<f32>() / lightness.len() as f32;
+    let dark: Vec<Color> = colors.iter().zip(&lightness).filter(|(_, l)| **l < mean).map(|(c, _)| *c).collect();
+    let light: Vec<Color> = colors.iter().zip(&lightness).filter(|(_, l)| **l >= mean).map(|(c, _)| *c).collect();
+    average(if dark.len() > light.len() { &dark } else { &light })
+}
+
+pub fn downscale(image: &Image, width: usize, height: usize, method: Downscale) -> Pixels
+{
+    let source = (image.width as usize, image.height as usize);
+    let mut result = vec![vec![TRANSPARENT; width]; height];
+
+    for (y, row) in result.iter_mut().enumerate()
+    {
+        for (x, pixel) in row.iter_mut().enumerate()
+        {
+            let (x0, y0, x1, y1) = block(x, y, source, (width, height));
+            let mut colors = Vec::with_capacity((x1 - x0) * (y1 - y0));
+            for sy in y0..y1
+            {
+                colors.extend(image.pixels[sy * source.0 + x0..sy * source.0 + x1].iter().map(|p| Color::from_u8(*p)));
+            }
+
+            //Pixel art has no half transparent pixels, the block is either filled or empty
+            let total = colors.len();
+            colors.retain(|c| c.a >= 0.5);
+            if colors.len() * 2 < total || colors.is_empty()
+            {
+                continue;
+            }
+
+            let color = match method
+            {
+                Downscale::Box => average(&colors),
+                Downscale::Median => median(&mut colors),
+                Downscale::EdgePreserving => edge_preserving(&colors),
+            };
+            *pixel = Color { a: 1.0, ..color }.to_u8();
+        }
+    }
+
+    result
+}
+
+//Median cut in OKLab, the colours of every box are averaged
+pub fn median_cut(pixels: &Pixels, count: usize) -> Vec<Color>
+{
+    let lab: Vec<[f32; 3]> = pixels.iter().flatten().filter(|p| p.3 > 0).map(|p|
+    {
+        let l = Color::from_u8(*p).to_oklab();
+        [l[0], l[1], l[2]]
+    }).collect();
+    if lab.is_empty()
+    {
+        return Vec::new();
+    }
+
+    let range = |colors: &[[f32; 3]], axis: usize|
+    {
+        let (min, max) = colors.iter().fold((f32::MAX, f32::MIN), |(min, max), c| (min.min(c[axis]), max.max(c[axis])));
+        max - min
+    };
+
+    let mut boxes = vec![lab];
+    while boxes.len() < count
+    {
+        //Box with the widest spread on any axis gets split at its median
+        let widest = boxes.iter().enumerate()
+            .filter(|(_, b)| b.len() > 1)
+            .flat_map(|(i, b)| (0..3).map(move |axis| (i, axis, range(b, axis))))
+            .max_by(|a, b| a.2.total_cmp(&b.2));
+        let (i, axis) = match widest
+        {
+            Some((i, axis, spread)) if spread > 0.0 => (i, axis),
+            _ => break,
+        };
+
+        let mut colors = boxes.swap_remove(i);
+        colors.sort_by(|a, b| a[axis].total_cmp(&b[axis]));
+        let upper = colors.split_off(colors.len() / 2);
+        boxes.push(colors);
+        boxes.push(upper);
+    }
+
+    boxes.iter().map(|b|
+    {
+        let n = b.len() as f32;
+        let sum = b.iter().fold([0.0; 3], |s, c| [s[0] + c[0], s[1] + c[1], s[2] + c[2]]);
+        Color::from_oklab([sum[0] / n, sum[1] / n, sum[2] / n, 1.0])
+    }).collect()
+}
+
+pub fn quantize(pixels: &Pixels, colors: &[Color], dither: Dither, strength: f32) -> Pixels
+{
+    let palette = Palette { colors: colors.to_vec(), ..Default::default() };
+    let height = pixels.len();
+    let width = if height > 0 { pixels[0].len() } else { 0 };
+    let mut error = vec![vec![[0.0f32; 3]; width + 2]; height + 1];
+    let mut result = pixels.clone();
+
+    for (y, row) in result.iter_mut().enumerate()
+    {
+        for (x, pixel) in row.iter_mut().enumerate()
+        {
+            if pixel.3 == 0
+            {
+                continue;
+            }
+
+            let c = Color::from_u8(*pixel);
+            let wanted = match dither
+            {
+                Dither::None => c,
+                Dither::Ordered =>
+                {
+                    let d = (BAYER[y % 4][x % 4] / 16.0 - 0.5) * strength * 0.25;
+                    Color::new(c.r + d, c.g + d, c.b + d, c.a).clamped()
+                }
+                Dither::FloydSteinberg =>
+                {
+                    let e = error[y][x + 1];
+                    Color::new(c.r + e[0], c.g + e[1], c.b + e[2], c.a).clamped()
+                }
+            };
+
+            let found = palette.nearest(wanted).unwrap_or(wanted);
+            if dither == Dither::FloydSteinberg
+            {
+                let e = [(wanted.r - found.r) * strength, (wanted.g - found.g) * strength, (wanted.b - found.b) * strength];
+                //Error buffer has a column of padding on both sides
+                for (dx, dy, weight) in [(2, 0, 7.0), (0, 1, 3.0), (1, 1, 5.0), (2, 1, 1.0)]
+                {
+                    for i in 0..3
+                    {
+                        error[y + dy][x + dx][i] += e[i] * weight / 16.0;
+                    }
+                }
+            }
+            *pixel = found.to_u8();
+        }
+    }
+
+    result
+}
+
+//Pixels that are clearly darker than a neighbour, or next to empty space
+pub fn detect_outline(pixels: &Pixels, threshold: f32) -> Vec<Vec<bool>>
+{
+    let lightness = |x: i32, y: i32| -> Option<f32>
+    {
+        //Negative coordinates wrap around to huge indices and are not found either
+        let p = pixels.get(y as usize)?.get(x as usize)?;
+        if p.3 == 0 { None } else { Some(Color::from_u8(*p).to_oklab()[0]) }
+    };
+
+    pixels.iter().enumerate().map(|(y, row)| (0..row.len()).map(|x|
+    {
+        let (x, y) = (x as i32, y as i32);
+        match lightness(x, y)
+        {
+            Some(l) => [(1, 0), (-1, 0), (0, 1), (0, -1)].iter().any(|(dx, dy)| match lightness(x + dx, y + dy)
+            {
+                Some(n) => n - l > threshold,
+                None => true,
+            }),
+            None => false,
+        }
+    }).collect()).collect()
+}
+
+//Whole pipeline, gives the pixels and the colours they use
+pub fn pixelate_image(image: &Image, params: &PixelateParams, palette: &Palette) -> (Pixels, Vec<Color>)
+{
+    let small = downscale(image, params.width.max(1) as usize, params.height.max(1) as usize, params.method);
+
+    let mut colors = if params.use_palette && !palette.colors.is_empty()
+    {
+        palette.colors.iter().map(|c| Color { a: 1.0, ..*c }).collect()
+    }
+    else
+    {
+        median_cut(&small, params.colors)
+    };
+    let mut result = quantize(&small, &colors, params.dither, params.dither_strength);
+
+    if params.outline
+    {
+        let outline = Color::from_array(params.outline_color).to_u8();
+        for (row, edges) in result.iter_mut().zip(detect_outline(&small, params.outline_threshold))
+        {
+            for (pixel, edge) in row.iter_mut().zip(edges)
+            {
+                if edge
+                {
+                    *pixel = outline;
+                }
+            }
+        }
+        colors.push(Color::from_u8(outline));
+    }
+
+    (result, colors)
+}
+
+#[derive(Default)]
+pub struct Pixelate
+{
+    pub open: bool,
+    pub path: String,
+    pub params: PixelateParams,
+    source: Option<Image>,
+    result: Option<(Pixels, Vec<Color>)>,
+    texture: Option<egui::TextureHandle>,
+    dirty: bool,//Preview is made on the next frame
+    changed: Option<f64>,//Time of the last parameter change the preview doesn't show yet
+}
+
+//Loads a big image and turns it into a new document, the parameters are previewed live
+pub fn pixelate_window(egui_ctx: &egui::Context, pixelate: &mut Pixelate, palette: &Palette) -> Option<Document>
+{
+    let mut open = pixelate.open;
+    let mut created = None;
+    let before = pixelate.params.clone();
+
+    egui::Window::new("Pixelate Image").open(&mut open).show(egui_ctx, |ui|
+    {
+        ui.label("Path: (without ending)");
+        ui.text_edit_singleline(&mut pixelate.path);
+        if ui.button("Load").clicked()
+        {
+            match crate::import_from_png(&pixelate.path)
+            {
+                Ok(image) =>
+                {
+                    //Keeps the aspect ratio at the current width
+                    let params = &mut pixelate.params;
+                    params.height = ((params.width as f32 * image.height as f32 / image.width.max(1) as f32).round() as i32).max(1);
+                    pixelate.source = Some(image);
+                    pixelate.dirty = true;
+                }
+                Err(_) => println!("Was not able to import image"),
+            }
+        }
+
+        let source = match &pixelate.source
+        {
+            Some(source) => source,
+            None => return,
+        };
+        let params = &mut pixelate.params;
+        ui.label(format!("Source: {} x {}", source.width, source.height));
+        ui.horizontal(|ui|
+        {
+            let width = ui.add(egui::DragValue::new(&mut params.width).clamp_range(1..=source.width as i32).prefix("w "));
+            if width.changed()
+            {
+                params.height = ((params.width as f32 * source.height as f32 / source.width.max(1) as f32).round() as i32).max(1);
+            }
+            ui.add(egui::DragValue::new(&mut params.height).clamp_range(1..=source.height as i32).prefix("h "));
+        });
+
+        ui.horizontal(|ui|
+        {
+            ui.radio_value(&mut params.method, Downscale::Box, "Box");
+            ui.radio_value(&mut params.method, Downscale::Median, "Median");
+            ui.radio_value(&mut params.method, Downscale::EdgePreserving, "Edge preserving");
+        });
+
+        ui.checkbox(&mut params.use_palette, "Use document palette");
+        ui.add_enabled(!params.use_palette, egui::Slider::new(&mut params.colors, 2..=64).text("Colors"));
+        ui.horizontal(|ui|
+        {
+            ui.radio_value(&mut params.dither, Dither::None, "No dither");
+            ui.radio_value(&mut params.dither, Dither::Ordered, "Ordered");
+            ui.radio_value(&mut params.dither, Dither::FloydSteinberg, "Floyd-Steinberg");
+        });
+        ui.add_enabled(params.dither != Dither::None, egui::Slider::new(&mut params.dither_strength, 0.0..=1.0).text("Dither strength"));
+
+        ui.horizontal(|ui|
+        {
+            ui.checkbox(&mut params.outline, "Outline");
+            ui.color_edit_button_rgba_unmultiplied(&mut params.outline_color);
+        });
+        ui.add_enabled(params.outline, egui::Slider::new(&mut params.outline_threshold, 0.01..=1.0).text("Edge threshold"));
+
+        if let Some(texture) = &pixelate.texture
+        {
+            let size = texture.size_vec2();
+            let fit = (PREVIEW_SIZE / size.x).min(PREVIEW_SIZE / size.y);
+            ui.image(texture.id(), size * fit);
+        }
+
+        if ui.button("Create").clicked()
+        {
+            if let Some((pixels, colors)) = &pixelate.result
+            {
+                //The preview waits for the parameters to settle, so the result can be older and its own size is used
+                let (width, height) = (pixels.first().map(|r| r.len()).unwrap_or(0) as i32, pixels.len() as i32);
+                let mut doc = Document::new(&pixelate.path, width, height, pixels.clone());
+                doc.palette.colors = colors.clone();
+                created = Some(doc);
+            }
+        }
+    });
+    pixelate.open = open;
+
+    //Dragging a value changes it every frame, the whole pipeline only runs once it settles
+    if pixelate.params != before
+    {
+        pixelate.changed = Some(get_time());
+    }
+    if let Some(time) = pixelate.changed
+    {
+        if get_time() - time > REBUILD_DELAY
+        {
+            pixelate.dirty = true;
+            pixelate.changed = None;
+        }
+    }
+    if let (true, Some(source)) = (pixelate.dirty && open, &pixelate.source)
+    {
+        let (pixels, colors) = pixelate_image(source, &pixelate.params, palette);
+        let flat: Vec<u8> = pixels.iter().flatten().flat_map(|p| [p.0, p.1, p.2, p.3]).collect();
+        let color_image = egui::ColorImage::from_rgba_unmultiplied([pixelate.params.width as usize, pixelate.params.height as usize], &flat);
+        pixelate.texture = Some(egui_ctx.load_texture("pixelate preview", color_image, egui::TextureOptions::NEAREST));
+        pixelate.result = Some((pixels, colors));
+        pixelate.dirty = false;
+    }
+
+    created
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    //Smooth gradient over the whole image with an empty top left quarter
+    fn source(width: u16, height: u16) -> Image
+    {
+        let pixels = (0..height as u32).flat_map(|y| (0..width as u32).map(move |x|
+        {
+            if x < width as u32 / 2 && y < height as u32 / 2
+            {
+                TRANSPARENT
+            }
+            else
+            {
+                ((x * 255 / width as u32) as u8, (y * 255 / height as u32) as u8, 128, 255)
+            }
+        })).collect();
+        Image::new(width, height, pixels)
+    }
+
+    fn colors_of(pixels: &Pixels) -> Vec<(u8, u8, u8, u8)>
+    {
+        let mut colors: Vec<(u8, u8, u8, u8)> = pixels.iter().flatten().copied().filter(|p| p.3 > 0).collect();
+        colors.sort();
+        colors.dedup();
+        colors
+    }
+
+    #[test]
+    fn downscale_has_target_size()
+    {
+        let image = source(100, 60);
+        for method in [Downscale::Box, Downscale::Median, Downscale::EdgePreserving]
+        {
+            for (width, height) in [(10, 6), (33, 17), (1, 1), (100, 60)]
+            {
+                let small = downscale(&image, width, height, method);
+                assert_eq!(small.len(), height, "{:?}", method);
+                assert!(small.iter().all(|row| row.len() == width), "{:?}", method);
+            }
+        }
+    }
+
+    #[test]
+    fn transparent_blocks_stay_transparent()
+    {
+        let image = source(80, 80);
+        let params = PixelateParams { width: 8, height: 8, colors: 4, dither: Dither::FloydSteinberg, ..Default::default() };
+        let (pixels, _) = pixelate_image(&image, &params, &Palette::default());
+        for (y, row) in pixels.iter().enumerate()
+        {
+            for (x, pixel) in row.iter().enumerate()
+            {
+                assert_eq!(pixel.3 == 0, x < 4 && y < 4, "{}, {}", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn median_cut_respects_count()
+    {
+        let small = downscale(&source(64, 64), 32, 32, Downscale::Box);
+        for count in [1, 2, 5, 16, 64]
+        {
+            let colors = median_cut(&small, count);
+            assert!(!colors.is_empty() && colors.len() <= count, "{} colors for {}", colors.len(), count);
+        }
+
+        //Fewer different colours than asked for, no box can be split further
+        let flat = vec![vec![(10, 20, 30, 255); 4]; 4];
+        assert_eq!(median_cut(&flat, 8).len(), 1);
+        assert!(median_cut(&vec![vec![TRANSPARENT; 4]; 4], 8).is_empty());
+    }
+
+    #[test]
+    fn quantize_uses_only_palette()
+    {
+        let small = downscale(&source(64, 64), 32, 32, Downscale::Box);
+        let palette = [Color::from_u8((255, 0, 0, 255)), Color::from_u8((0, 255, 0, 255)), Color::from_u8((20, 20, 120, 255))];
+        let allowed: Vec<(u8, u8, u8, u8)> = palette.iter().map(|c| c.to_u8()).collect();
+
+        for dither in [Dither::None, Dither::Ordered, Dither::FloydSteinberg]
+        {
+            let result = quantize(&small, &palette, dither, 1.0);
+            for color in colors_of(&result)
+            {
+                assert!(allowed.contains(&color), "{:?} with {:?}", color, dither);
+            }
+            //Empty pixels are skipped
+            assert!(result.iter().flatten().zip(small.iter().flatten()).all(|(a, b)| (a.3 == 0) == (b.3 == 0)));
+        }
+    }
+
+    #[test]
+    fn outline_finds_edges()
+    {
+        let dark = (20, 20, 20, 255);
+        let light = (230, 230, 230, 255);
+        let mut pixels = vec![vec![light; 5]; 5];
+        pixels[2][2] = dark;
+        let edges = detect_outline(&pixels, 0.2);
+
+        //Darker than a neighbour, or on the border of the image
+        assert!(edges[2][2]);
+        assert!(!edges[1][1] && !edges[2][1] && !edges[3][3]);
+        assert!(edges[0][2] && edges[4][4]);
+    }
+}
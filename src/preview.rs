@@ -0,0 +1,117 @@
+use macroquad::prelude::{get_time, screen_height, screen_width};
+
+use crate::canvas;
+use crate::document::Document;
+use crate::layout;
+use crate::palette::CycleMap;
+use crate::view_filter::ViewFilter;
+
+//Small window that shows the image at its real size and works as a minimap
+pub struct Preview
+{
+    pub open: bool,
+    pub zoom: usize,
+    pub tile: bool,
+    texture: Option<egui::TextureHandle>,
+    version: u64,
+}
+
+impl Default for Preview
+{
+    fn default() -> Self
+    {
+        Preview
+        {
+            open: false,
+            zoom: 1,
+            tile: false,
+            texture: None,
+            version: u64::MAX,
+        }
+    }
+}
+
+const MINIMAP_SIZE: f32 = 160.0;
+
+//Floating window, or a section of the side panel when docked
+pub fn preview_window(egui_ctx: &egui::Context, panel: Option<&mut egui::Ui>, preview: &mut Preview, doc: &mut Document, animate: &mut bool)
+{
+    if !preview.open || doc.pixels.is_empty()
+    {
+        return;
+    }
+    let (canvas, pixels, viewport) = (&doc.canvas, &doc.pixels, &mut doc.viewport);
+    let cycle_map = if *animate { doc.palette.cycle_map(get_time() as f32) } else { CycleMap::new() };
+
+    //Only copied again when the canvas texture changed
+    if preview.version != canvas.version() || preview.texture.as_ref().map(|t| t.size()) != Some([canvas.width, canvas.height])
+    {
+        let bytes = canvas::region_bytes(pixels, (0, 0, canvas.width, canvas.height), ViewFilter::None, &cycle_map);
+        let image = egui::ColorImage::from_rgba_unmultiplied([canvas.width, canvas.height], &bytes);
+        match &mut preview.texture
+        {
+            Some(texture) => texture.set(image, egui::TextureOptions::NEAREST),
+            None => preview.texture = Some(egui_ctx.load_texture("preview", image, egui::TextureOptions::NEAREST)),
+        }
+        preview.version = canvas.version();
+    }
+    let texture = match &preview.texture
+    {
+        Some(texture) => texture.id(),
+        None => return,
+    };
+
+    let size = egui::vec2(canvas.width as f32, canvas.height as f32);
+    let screen = (screen_width(), screen_height());
+    let mut open = preview.open;
+    layout::closable_section(egui_ctx, panel, "Preview", &mut open, |ui|
+    {
+        ui.horizontal(|ui|
+        {
+            ui.selectable_value(&mut preview.zoom, 1, "1x");
+            ui.selectable_value(&mut preview.zoom, 2, "2x");
+            ui.selectable_value(&mut preview.zoom, 4, "4x");
+            ui.checkbox(&mut preview.tile, "Tile");
+            ui.checkbox(animate, "Animate");
+        });
+
+        egui::ScrollArea::both().max_height(400.0).show(ui, |ui|
+        {
+            let tiles = if preview.tile { 3 } else { 1 };
+            let tile_size = size * preview.zoom as f32;
+            let (rect, _) = ui.allocate_exact_size(tile_size * tiles as f32, egui::Sense::hover());
+            for ty in 0..tiles
+            {
+                for tx in 0..tiles
+                {
+                    let min = rect.min + egui::vec2(tx as f32 * tile_size.x, ty as f32 * tile_size.y);
+                    let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+                    ui.painter().image(texture, egui::Rect::from_min_size(min, tile_size), uv, egui::Color32::WHITE);
+                }
+            }
+        });
+        ui.separator();
+
+        //Minimap, click or drag to move the view
+        let fit = (MINIMAP_SIZE / size.x).min(MINIMAP_SIZE / size.y);
+        let (rect, response) = ui.allocate_exact_size(size * fit, egui::Sense::click_and_drag());
+        let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+        ui.painter().rect_filled(rect, 0.0, egui::Color32::from_gray(60));
+        ui.painter().image(texture, rect, uv, egui::Color32::WHITE);
+
+        let top_left = viewport.screen_to_canvas((0.0, 0.0));
+        let bottom_right = viewport.screen_to_canvas(screen);
+        let view = egui::Rect::from_min_max(rect.min + egui::vec2(top_left.0, top_left.1) * fit, rect.min + egui::vec2(bottom_right.0, bottom_right.1) * fit);
+        ui.painter().rect_stroke(view.intersect(rect), 0.0, egui::Stroke::new(1.0, egui::Color32::RED));
+
+        if response.clicked() || response.dragged()
+        {
+            if let Some(pos) = response.interact_pointer_pos()
+            {
+                let target = ((pos.x - rect.min.x) / fit, (pos.y - rect.min.y) / fit);
+                viewport.offset = (screen.0 / 2.0 - target.0 * viewport.scale, screen.1 / 2.0 - target.1 * viewport.scale);
+            }
+        }
+    });
+    preview.open = open;
+}
@@ -0,0 +1,263 @@
+use image::RgbaImage;
+use macroquad::prelude::*;
+
+use crate::viewport::Viewport;
+
+//Image shown next to or on top of the canvas for tracing and colour picking, never part of the pixels
+#[derive(Clone)]
+pub struct Reference
+{
+    pub path: String,
+    pub position: (f32, f32),//Canvas pixels
+    pub scale: f32,
+    pub opacity: f32,
+    pub locked: bool,
+    pub above: bool,//Drawn over the canvas instead of under it
+    pub visible: bool,
+    missing: bool,//Kept in the project even when the file is gone
+    image: Option<RgbaImage>,
+    texture: Option<Texture2D>,
+    egui_texture: Option<egui::TextureHandle>,
+}
+
+impl Reference
+{
+    pub fn new(path: &str) -> Reference
+    {
+        Reference
+        {
+            path: path.to_string(),
+            position: (0.0, 0.0),
+            scale: 1.0,
+            opacity: 0.5,
+            locked: false,
+            above: false,
+            visible: true,
+            missing: false,
+            image: None,
+            texture: None,
+            egui_texture: None,
+        }
+    }
+
+    //Textures can only be made once the window exists, so this happens on first use
+    fn load(&mut self, egui_ctx: Option<&egui::Context>)
+    {
+        if self.image.is_none() && !self.missing
+        {
+            match image::open(&self.path)
+            {
+                Ok(img) =>
+                {
+                    let img = img.to_rgba8();
+                    let texture = Texture2D::from_rgba8(img.width() as u16, img.height() as u16, img.as_raw());
+                    self.texture = Some(texture);
+                    self.image = Some(img);
+                }
+                Err(_) =>
+                {
+                    println!("Was not able to load reference {}", self.path);
+                    self.missing = true;
+                }
+            }
+        }
+
+        if let (Some(ctx), Some(img), None) = (egui_ctx, &self.image, &self.egui_texture)
+        {
+            let color_image = egui::ColorImage::from_rgba_unmultiplied([img.width() as usize, img.height() as usize], img.as_raw());
+            self.egui_texture = Some(ctx.load_texture(format!("reference {}", self.path), color_image, egui::TextureOptions::NEAREST));
+        }
+    }
+
+    pub fn size(&self) -> (f32, f32)
+    {
+        match &self.image
+        {
+            Some(img) => (img.width() as f32 * self.scale, img.height() as f32 * self.scale),
+            None => (0.0, 0.0),
+        }
+    }
+
+    //Canvas position inside of the reference
+    pub fn contains(&self, pos: (f32, f32)) -> bool
+    {
+        let size = self.size();
+        pos.0 >= self.position.0 && pos.1 >= self.position.1 && pos.0 < self.position.0 + size.0 && pos.1 < self.position.1 + size.1
+    }
+}
+
+pub fn draw_references(references: &mut [Reference], viewport: &Viewport, above: bool)
+{
+    for reference in references.iter_mut().filter(|r| r.visible && r.above == above)
+    {
+        reference.load(None);
+        if let Some(texture) = reference.texture
+        {
+            let p = viewport.canvas_to_screen(reference.position);
+            let size = reference.size();
+            draw_texture_ex(texture, p.0, p.1, Color::new(1.0, 1.0, 1.0, reference.opacity), DrawTextureParams
+            {
+                dest_size: Some(vec2(size.0 * viewport.scale, size.1 * viewport.scale)),
+                ..Default::default()
+            });
+        }
+    }
+}
+
+//Alt + drag moves the topmost unlocked reference under the mouse
+pub fn drag_references(references: &mut [Reference], viewport: &Viewport, dragging: &mut Option<usize>, last_mouse: (f32, f32))
+{
+    let mouse = mouse_position();
+    let alt = is_key_down(KeyCode::LeftAlt) || is_key_down(KeyCode::RightAlt);
+
+    if alt && is_mouse_button_pressed(MouseButton::Left)
+    {
+        let pos = viewport.screen_to_canvas(mouse);
+        *dragging = references.iter().rposition(|r| r.visible && !r.locked && r.contains(pos));
+    }
+
+    match *dragging
+    {
+        Some(i) if is_mouse_button_down(MouseButton::Left) && i < references.len() =>
+        {
+            let r = &mut references[i];
+            r.position = (r.position.0 + (mouse.0 - last_mouse.0) / viewport.scale, r.position.1 + (mouse.1 - last_mouse.1) / viewport.scale);
+        }
+        _ => *dragging = None,
+    }
+}
+
+#[derive(Default)]
+pub struct ReferencePanel
+{
+    pub open: bool,
+    pub new_path: String,
+    pub current: usize,
+}
+
+//List of references, plus a view of one of them that can be clicked to pick a colour
+pub fn reference_window(egui_ctx: &egui::Context, panel: &mut ReferencePanel, references: &mut Vec<Reference>, rgba: &mut [f32; 4])
+{
+    let mut open = panel.open;
+    egui::Window::new("References").open(&mut open).show(egui_ctx, |ui|
+    {
+        ui.label("Path: (with ending)");
+        ui.text_edit_singleline(&mut panel.new_path);
+        if ui.button("Add Reference").clicked()
+        {
+            references.push(Reference::new(&panel.new_path));
+            panel.current = references.len() - 1;
+        }
+        ui.label("Alt + drag moves an unlocked reference on the canvas");
+        ui.separator();
+
+        let mut remove = None;
+        for (i, reference) in references.iter_mut().enumerate()
+        {
+            reference.load(Some(egui_ctx));
+            ui.horizontal(|ui|
+            {
+                ui.radio_value(&mut panel.current, i, "");
+                ui.checkbox(&mut reference.visible, "");
+                ui.label(if reference.missing { format!("{} (missing)", reference.path) } else { reference.path.clone() });
+                ui.checkbox(&mut reference.locked, "Lock");
+                if ui.button("X").clicked()
+                {
+                    remove = Some(i);
+                }
+            });
+            ui.add_enabled_ui(!reference.locked, |ui|
+            {
+                ui.horizontal(|ui|
+                {
+                    ui.add(egui::DragValue::new(&mut reference.position.0).prefix("x "));
+                    ui.add(egui::DragValue::new(&mut reference.position.1).prefix("y "));
+                    ui.add(egui::DragValue::new(&mut reference.scale).clamp_range(0.01..=64.0).speed(0.01).prefix("scale "));
+                });
+                ui.horizontal(|ui|
+                {
+                    ui.add(egui::Slider::new(&mut reference.opacity, 0.0..=1.0).text("Opacity"));
+                    ui.checkbox(&mut reference.above, "Above canvas");
+                });
+            });
+        }
+        if let Some(i) = remove
+        {
+            references.remove(i);
+        }
+
+        if let Some(reference) = references.get(panel.current)
+        {
+            if let (Some(texture), Some(img)) = (&reference.egui_texture, &reference.image)
+            {
+                ui.separator();
+                ui.label("Click to pick a color");
+                let fit = (256.0 / img.width() as f32).min(256.0 / img.height() as f32);
+                let response = ui.add(egui::Image::new(texture.id(), egui::vec2(img.width() as f32 * fit, img.height() as f32 * fit)).sense(egui::Sense::click()));
+                if let (true, Some(pos)) = (response.clicked(), response.interact_pointer_pos())
+                {
+                    let x = ((pos.x - response.rect.min.x) / fit) as u32;
+                    let y = ((pos.y - response.rect.min.y) / fit) as u32;
+                    if x < img.width() && y < img.height()
+                    {
+                        let p = img.get_pixel(x, y).0;
+                        *rgba = crate::color::Color::from_u8((p[0], p[1], p[2], p[3])).to_array();
+                    }
+                }
+            }
+        }
+    });
+    panel.open = open;
+}
+
+pub fn to_bytes(references: &[Reference]) -> Vec<u8>
+{
+    let mut bytes = Vec::new();
+
+    bytes.extend((references.len() as u16).to_le_bytes());
+    for r in references
+    {
+        bytes.extend((r.path.len() as u16).to_le_bytes());
+        bytes.extend(r.path.as_bytes());
+        bytes.extend(r.position.0.to_le_bytes());
+        bytes.extend(r.position.1.to_le_bytes());
+        bytes.extend(r.scale.to_le_bytes());
+        bytes.extend(r.opacity.to_le_bytes());
+        bytes.push(r.locked as u8 | (r.above as u8) << 1 | (r.visible as u8) << 2);
+    }
+
+    bytes
+}
+
+pub fn from_bytes(bytes: &[u8]) -> Option<Vec<Reference>>
+{
+    let mut references = Vec::new();
+    let mut pos = 0;
+
+    let count = u16::from_le_bytes(bytes.get(pos..pos+2)?.try_into().ok()?);
+    pos += 2;
+    for _ in 0..count
+    {
+        let len = u16::from_le_bytes(bytes.get(pos..pos+2)?.try_into().ok()?) as usize;
+        pos += 2;
+        let path = String::from_utf8(bytes.get(pos..pos+len)?.to_vec()).ok()?;
+        pos += len;
+
+        let f = |i: usize| -> Option<f32> { Some(f32::from_le_bytes(bytes.get(pos+i*4..pos+i*4+4)?.try_into().ok()?)) };
+        let mut reference = Reference::new(&path);
+        reference.position = (f(0)?, f(1)?);
+        reference.scale = f(2)?;
+        reference.opacity = f(3)?;
+        pos += 16;
+
+        let flags = *bytes.get(pos)?;
+        reference.locked = flags & 1 != 0;
+        reference.above = flags & 2 != 0;
+        reference.visible = flags & 4 != 0;
+        pos += 1;
+
+        references.push(reference);
+    }
+
+    Some(references)
+}
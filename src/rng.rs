@@ -0,0 +1,62 @@
+//Seeds are edited as f64 in the UI, larger ones could not be shown or typed exactly
+pub const MAX_SEED: u64 = 1 << 53;
+
+//Small seeded random numbers (SplitMix64), the same seed always gives the same sequence
+#[derive(Clone, Copy, Debug)]
+pub struct Rng
+{
+    state: u64,
+}
+
+impl Rng
+{
+    pub fn new(seed: u64) -> Rng
+    {
+        Rng
+        {
+            state: seed
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64
+    {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        mix(self.state)
+    }
+
+    //0.0..1.0
+    pub fn next_f32(&mut self) -> f32
+    {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    //0..max, 0 when max is 0
+    pub fn below(&mut self, max: usize) -> usize
+    {
+        if max == 0
+        {
+            return 0;
+        }
+        (self.next_u64() % max as u64) as usize
+    }
+}
+
+//Finalizer of SplitMix64, spreads every input bit over the whole output
+fn mix(mut z: u64) -> u64
+{
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+//Random value for a grid point, used where noise needs the same value every time it looks at a point
+pub fn hash(x: i32, y: i32, seed: u64) -> u64
+{
+    mix(seed ^ mix((x as u32 as u64) << 32 | y as u32 as u64))
+}
+
+//0.0..1.0 version of hash
+pub fn hash_f32(x: i32, y: i32, seed: u64) -> f32
+{
+    (hash(x, y, seed) >> 40) as f32 / (1u64 << 24) as f32
+}
@@ -0,0 +1,243 @@
+use macroquad::prelude::*;
+
+use crate::viewport::Viewport;
+
+pub const RULER_SIZE: f32 = 24.0;
+const SNAP_DISTANCE: f32 = 8.0;//Screen pixels
+const GUIDE_COLOR: Color = Color::new(1.0, 0.2, 0.6, 0.9);
+
+//Guides are positions between pixels (edges), like the selection
+#[derive(Default)]
+pub struct Guides
+{
+    pub show_rulers: bool,
+    pub snap: bool,
+    pub horizontal: Vec<i32>,//y positions
+    pub vertical: Vec<i32>,//x positions
+    dragging: Option<(bool, usize)>,//(horizontal, index)
+}
+
+impl Guides
+{
+    //Per axis: the closest guide if it is near pos, otherwise the fallback
+    pub fn snap(&self, pos: (f32, f32), scale: f32, fallback: (i32, i32)) -> (i32, i32)
+    {
+        if !self.snap
+        {
+            return fallback;
+        }
+
+        let snap_axis = |guides: &[i32], v: f32, fallback: i32|
+        {
+            guides.iter().copied()
+                .filter(|g| (*g as f32 - v).abs() * scale <= SNAP_DISTANCE)
+                .min_by(|a, b| (*a as f32 - v).abs().total_cmp(&(*b as f32 - v).abs()))
+                .unwrap_or(fallback)
+        };
+        (snap_axis(&self.vertical, pos.0, fallback.0), snap_axis(&self.horizontal, pos.1, fallback.1))
+    }
+}
+
+pub fn guides_ui(ui: &mut egui::Ui, guides: &mut Guides)
+{
+    ui.horizontal(|ui|
+    {
+        ui.checkbox(&mut guides.show_rulers, "Rulers");
+        ui.checkbox(&mut guides.snap, "Snap to guides");
+    });
+    if guides.show_rulers
+    {
+        ui.label("Drag from a ruler to add a guide, back onto it to remove");
+        if ui.button("Clear Guides").clicked()
+        {
+            guides.horizontal.clear();
+            guides.vertical.clear();
+        }
+    }
+}
+
+//Returns true while the mouse is used by the rulers, so the tools leave it alone
+//area is the part of the screen the canvas is shown in
+pub fn update_guides(guides: &mut Guides, viewport: &Viewport, area: Rect) -> bool
+{
+    if !guides.show_rulers
+    {
+        guides.dragging = None;
+        return false;
+    }
+
+    let mouse = mouse_position();
+    let inside = area.contains(vec2(mouse.0, mouse.1));
+    let on_top = inside && mouse.1 < area.y + RULER_SIZE;
+    let on_left = inside && mouse.0 < area.x + RULER_SIZE;
+    let c = viewport.screen_to_canvas(mouse);
+    let edge = (c.0.round() as i32, c.1.round() as i32);
+
+    if is_mouse_button_pressed(MouseButton::Left) && (on_top != on_left)
+    {
+        //The top ruler shows the vertical guides and the left one the horizontal guides,
+        //pressing on one of those markers picks it up, anywhere else starts a new guide
+        let near = |g: &i32, v: f32| (*g as f32 - v).abs() * viewport.scale <= 4.0;
+        let existing = if on_top
+        {
+            guides.vertical.iter().position(|g| near(g, c.0)).map(|i| (false, i))
+        }
+        else
+        {
+            guides.horizontal.iter().position(|g| near(g, c.1)).map(|i| (true, i))
+        };
+
+        guides.dragging = existing.or_else(||
+        {
+            if on_top
+            {
+                guides.horizontal.push(edge.1);
+                Some((true, guides.horizontal.len() - 1))
+            }
+            else
+            {
+                guides.vertical.push(edge.0);
+                Some((false, guides.vertical.len() - 1))
+            }
+        });
+    }
+
+    match guides.dragging
+    {
+        Some((horizontal, i)) =>
+        {
+            if is_mouse_button_down(MouseButton::Left)
+            {
+                if horizontal
+                {
+                    guides.horizontal[i] = edge.1;
+                }
+                else
+                {
+                    guides.vertical[i] = edge.0;
+                }
+            }
+            else
+            {
+                //Dropped onto a ruler
+                if on_top || on_left
+                {
+                    if horizontal
+                    {
+                        guides.horizontal.remove(i);
+                    }
+                    else
+                    {
+                        guides.vertical.remove(i);
+                    }
+                }
+                guides.dragging = None;
+            }
+            true
+        }
+        None => on_top || on_left,
+    }
+}
+
+pub fn draw_guides(guides: &Guides, viewport: &Viewport, area: Rect)
+{
+    for y in guides.horizontal.iter()
+    {
+        let sy = viewport.canvas_to_screen((0.0, *y as f32)).1;
+        draw_line(area.x, sy, area.x + area.w, sy, 1.0, GUIDE_COLOR);
+    }
+    for x in guides.vertical.iter()
+    {
+        let sx = viewport.canvas_to_screen((*x as f32, 0.0)).0;
+        draw_line(sx, area.y, sx, area.y + area.h, 1.0, GUIDE_COLOR);
+    }
+}
+
+//Distance between labelled ticks, at least 50 screen pixels apart
+fn tick_step(scale: f32) -> i32
+{
+    let mut step = 1;
+    loop
+    {
+        for m in [1, 2, 5]
+        {
+            if (step * m) as f32 * scale >= 50.0
+            {
+                return step * m;
+            }
+        }
+        step *= 10;
+    }
+}
+
+//Rulers along the top and left edge of area, counted in canvas pixels
+pub fn draw_rulers(guides: &Guides, viewport: &Viewport, area: Rect)
+{
+    if !guides.show_rulers
+    {
+        return;
+    }
+
+    let (left, top) = (area.x, area.y);
+    let background = Color::from_rgba(30, 30, 36, 255);
+    let tick = Color::from_rgba(160, 160, 170, 255);
+
+    draw_rectangle(left, top, area.w, RULER_SIZE, background);
+    draw_rectangle(left, top, RULER_SIZE, area.h, background);
+
+    let major = tick_step(viewport.scale);
+    let minor = if major % 5 == 0 { major / 5 } else { major / 2 }.max(1);
+    let start = viewport.screen_to_canvas((left + RULER_SIZE, top + RULER_SIZE));
+    let end = viewport.screen_to_canvas((left + area.w, top + area.h));
+
+    //Top
+    let mut x = (start.0 / minor as f32).floor() as i32 * minor;
+    while (x as f32) <= end.0
+    {
+        let sx = viewport.canvas_to_screen((x as f32, 0.0)).0;
+        if x % major == 0
+        {
+            draw_line(sx, top, sx, top + RULER_SIZE, 1.0, tick);
+            draw_text(&x.to_string(), sx + 2.0, top + 10.0, 14.0, tick);
+        }
+        else if minor as f32 * viewport.scale >= 4.0
+        {
+            draw_line(sx, top + RULER_SIZE * 0.7, sx, top + RULER_SIZE, 1.0, tick);
+        }
+        x += minor;
+    }
+
+    //Left
+    let mut y = (start.1 / minor as f32).floor() as i32 * minor;
+    while (y as f32) <= end.1
+    {
+        let sy = viewport.canvas_to_screen((0.0, y as f32)).1;
+        if y % major == 0
+        {
+            draw_line(left, sy, left + RULER_SIZE, sy, 1.0, tick);
+            draw_text(&y.to_string(), left + 2.0, sy + 12.0, 14.0, tick);
+        }
+        else if minor as f32 * viewport.scale >= 4.0
+        {
+            draw_line(left + RULER_SIZE * 0.7, sy, left + RULER_SIZE, sy, 1.0, tick);
+        }
+        y += minor;
+    }
+
+    //Guide markers and the cursor
+    for x in guides.vertical.iter()
+    {
+        let sx = viewport.canvas_to_screen((*x as f32, 0.0)).0;
+        draw_triangle(vec2(sx - 4.0, top + RULER_SIZE - 6.0), vec2(sx + 4.0, top + RULER_SIZE - 6.0), vec2(sx, top + RULER_SIZE), GUIDE_COLOR);
+    }
+    for y in guides.horizontal.iter()
+    {
+        let sy = viewport.canvas_to_screen((0.0, *y as f32)).1;
+        draw_triangle(vec2(left + RULER_SIZE - 6.0, sy - 4.0), vec2(left + RULER_SIZE - 6.0, sy + 4.0), vec2(left + RULER_SIZE, sy), GUIDE_COLOR);
+    }
+    let mouse = mouse_position();
+    draw_line(mouse.0, top, mouse.0, top + RULER_SIZE, 1.0, WHITE);
+    draw_line(left, mouse.1, left + RULER_SIZE, mouse.1, 1.0, WHITE);
+
+    draw_rectangle(left, top, RULER_SIZE, RULER_SIZE, background);
+}
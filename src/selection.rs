@@ -0,0 +1,100 @@
+//Per pixel selection mask, same size as the canvas
+pub struct Selection
+{
+    pub width: usize,
+    pub height: usize,
+    pub mask: Vec<bool>,
+}
+
+impl Selection
+{
+    pub fn new(width: usize, height: usize) -> Selection
+    {
+        Selection
+        {
+            width,
+            height,
+            mask: vec![false; width * height]
+        }
+    }
+
+    //Edge coordinates, x1 and y1 are exclusive, gets clamped to the canvas
+    pub fn from_rect(width: usize, height: usize, x0: i32, y0: i32, x1: i32, y1: i32) -> Selection
+    {
+        let mut selection = Selection::new(width, height);
+
+        for y in y0.max(0)..y1.min(height as i32)
+        {
+            for x in x0.max(0)..x1.min(width as i32)
+            {
+                selection.mask[y as usize * width + x as usize] = true;
+            }
+        }
+
+        selection
+    }
+
+    //Selects every pixel that has exactly this colour
+    pub fn from_color(pixels: &[Vec<(u8, u8, u8, u8)>], color: (u8, u8, u8, u8)) -> Selection
+    {
+        let height = pixels.len();
+        let width = if height > 0 { pixels[0].len() } else { 0 };
+        let mut selection = Selection::new(width, height);
+
+        for (y, row) in pixels.iter().enumerate()
+        {
+            for (x, pixel) in row.iter().enumerate()
+            {
+                if *pixel == color
+                {
+                    selection.mask[y * width + x] = true;
+                }
+            }
+        }
+
+        selection
+    }
+
+    pub fn contains(&self, x: i32, y: i32) -> bool
+    {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height
+        {
+            return false;
+        }
+
+        self.mask[y as usize * self.width + x as usize]
+    }
+
+    //Smallest rectangle around the selected pixels as edges (x0, y0, x1, y1), None if nothing is selected
+    pub fn bounds(&self) -> Option<(i32, i32, i32, i32)>
+    {
+        let mut bounds: Option<(i32, i32, i32, i32)> = None;
+
+        for (i, _) in self.mask.iter().enumerate().filter(|(_, selected)| **selected)
+        {
+            let (x, y) = ((i % self.width) as i32, (i / self.width) as i32);
+            bounds = Some(match bounds
+            {
+                Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x + 1), y1.max(y + 1)),
+                None => (x, y, x + 1, y + 1),
+            });
+        }
+
+        bounds
+    }
+
+    pub fn count(&self) -> usize
+    {
+        self.mask.iter().filter(|selected| **selected).count()
+    }
+}
+
+//Without a selection the whole canvas can be edited
+pub fn editable(selection: &Option<Selection>, x: i32, y: i32) -> bool
+{
+    match selection
+    {
+        Some(s) => s.contains(x, y),
+        None => true,
+    }
+}
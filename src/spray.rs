@@ -0,0 +1,183 @@
+use crate::color::Color;
+use crate::rng::Rng;
+use crate::selection::{self, Selection};
+
+type Pixels = Vec<Vec<(u8, u8, u8, u8)>>;
+
+//Scatters single pixels inside of the brush, part of the tool state of a document
+pub struct SprayTool
+{
+    pub active: bool,
+    pub density: f32,//Share of the brush area that gets a pixel every second
+    pub use_palette: bool,//Random palette colour for every pixel instead of the current colour
+    pub seed: u64,
+    rng: Rng,
+    pending: f32,//Pixels left over from earlier frames, so low densities still spray at high frame rates
+}
+
+impl Default for SprayTool
+{
+    fn default() -> Self
+    {
+        SprayTool
+        {
+            active: false,
+            density: 6.0,
+            use_palette: false,
+            seed: 1,
+            rng: Rng::new(1),
+            pending: 0.0,
+        }
+    }
+}
+
+impl SprayTool
+{
+    //Starts the random sequence over, spraying the same path again gives the same pixels
+    pub fn reseed(&mut self)
+    {
+        self.rng = Rng::new(self.seed);
+        self.pending = 0.0;
+    }
+
+    //One frame of spraying that took dt seconds, returns the dirty rectangle (x, y, w, h)
+    pub fn spray(&mut self, pixels: &mut Pixels, selection: &Option<Selection>, center: (i32, i32), half_brush_size: i32, colors: &[Color], dt: f32) -> (i32, i32, i32, i32)
+    {
+        let radius = half_brush_size as f32 + 0.5;
+        self.pending += std::f32::consts::PI * radius * radius * self.density * dt.max(0.0);
+        let count = self.pending.floor() as usize;
+        self.pending -= count as f32;
+
+        for _ in 0..count
+        {
+            //Square root keeps the points evenly spread over the circle instead of bunched in the middle
+            let angle = self.rng.next_f32() * std::f32::consts::TAU;
+            let distance = self.rng.next_f32().sqrt() * radius;
+            let x = center.0 + (angle.cos() * distance).round() as i32;
+            let y = center.1 + (angle.sin() * distance).round() as i32;
+            let color = colors[self.rng.below(colors.len())].to_u8();
+
+            if y >= 0 && (y as usize) < pixels.len() && x >= 0 && (x as usize) < pixels[y as usize].len() && selection::editable(selection, x, y)
+            {
+                pixels[y as usize][x as usize] = color;
+            }
+        }
+
+        (center.0 - half_brush_size - 1, center.1 - half_brush_size - 1, half_brush_size * 2 + 3, half_brush_size * 2 + 3)
+    }
+}
+
+pub fn spray_ui(ui: &mut egui::Ui, spray: &mut SprayTool)
+{
+    ui.add(egui::Slider::new(&mut spray.density, 0.5..=60.0).logarithmic(true).text("Density per second"));
+    ui.checkbox(&mut spray.use_palette, "Random palette color");
+    ui.horizontal(|ui|
+    {
+        if ui.add(egui::DragValue::new(&mut spray.seed).prefix("seed ")).changed()
+        {
+            spray.reseed();
+        }
+        if ui.button("Restart").clicked()
+        {
+            spray.reseed();
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    const COLORS: [Color; 2] = [Color { r: 1.0, g: 0.0, b: 0.0, a: 1.0 }, Color { r: 0.0, g: 0.0, b: 1.0, a: 1.0 }];
+    const BLANK: (u8, u8, u8, u8) = (0, 0, 0, 0);
+    const FRAME: f32 = 1.0 / 60.0;
+
+    fn sprayed(tool: &mut SprayTool, selection: &Option<Selection>) -> Pixels
+    {
+        let mut pixels = vec![vec![BLANK; 40]; 40];
+        for (i, center) in [(20, 20), (21, 20), (22, 21), (3, 38)].into_iter().enumerate()
+        {
+            tool.spray(&mut pixels, selection, center, 5, &COLORS, FRAME * (i + 1) as f32);
+        }
+        pixels
+    }
+
+    #[test]
+    fn reseed_repeats_pixels()
+    {
+        let mut tool = SprayTool { use_palette: true, seed: 42, ..Default::default() };
+        tool.reseed();
+        let first = sprayed(&mut tool, &None);
+        assert!(first != sprayed(&mut tool, &None));
+        tool.reseed();
+        assert!(first == sprayed(&mut tool, &None));
+    }
+
+    #[test]
+    fn stays_inside_radius()
+    {
+        let mut tool = SprayTool { density: 60.0, ..Default::default() };
+        let mut pixels = vec![vec![BLANK; 40]; 40];
+        let dirty = tool.spray(&mut pixels, &None, (20, 20), 4, &COLORS, 1.0);
+        let mut count = 0;
+        for (y, row) in pixels.iter().enumerate()
+        {
+            for (x, pixel) in row.iter().enumerate().filter(|(_, p)| **p != BLANK)
+            {
+                let (dx, dy) = (x as f32 - 20.0, y as f32 - 20.0);
+                assert!((dx * dx + dy * dy).sqrt() <= 4.5 + 0.75, "{}, {}", x, y);
+                assert!(x as i32 >= dirty.0 && y as i32 >= dirty.1 && (x as i32) < dirty.0 + dirty.2 && (y as i32) < dirty.1 + dirty.3);
+                assert!(COLORS.iter().any(|c| c.to_u8() == *pixel));
+                count += 1;
+            }
+        }
+        assert!(count > 0);
+    }
+
+    #[test]
+    fn stays_inside_selection()
+    {
+        let mut selection = Selection::new(40, 40);
+        for y in 18..22
+        {
+            for x in 15..20
+            {
+                selection.mask[y * 40 + x] = true;
+            }
+        }
+        let selection = Some(selection);
+        let mut tool = SprayTool { density: 60.0, ..Default::default() };
+        let pixels = sprayed(&mut tool, &selection);
+
+        for (y, row) in pixels.iter().enumerate()
+        {
+            for (x, pixel) in row.iter().enumerate().filter(|(_, p)| **p != BLANK)
+            {
+                assert!(selection::editable(&selection, x as i32, y as i32), "{:?} at {}, {}", pixel, x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn density_is_per_second()
+    {
+        //One second of spraying at different frame rates
+        let after_second = |frames: usize|
+        {
+            let mut tool = SprayTool::default();
+            let mut pixels = vec![vec![BLANK; 40]; 40];
+            for _ in 0..frames
+            {
+                tool.spray(&mut pixels, &None, (20, 20), 3, &COLORS, 1.0 / frames as f32);
+            }
+            (tool.pending, tool.rng.next_u64())
+        };
+
+        //Every sprayed pixel takes the same number of random values, so equal sequences mean equal counts
+        let (pending_30, next_30) = after_second(30);
+        let (pending_144, next_144) = after_second(144);
+        assert!((pending_30 - pending_144).abs() < 0.01);
+        assert_eq!(next_30, next_144);
+    }
+}
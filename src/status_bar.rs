@@ -0,0 +1,44 @@
+use crate::color::Color;
+use crate::palette;
+use crate::selection::Selection;
+use crate::viewport::Viewport;
+
+//Bottom bar with the pixel under the cursor, selection, zoom and canvas size
+pub fn status_bar(egui_ctx: &egui::Context, pixels: &[Vec<(u8, u8, u8, u8)>], cursor: (i32, i32), selection: &Option<Selection>, viewport: &Viewport, canvas: (i32, i32))
+{
+    egui::TopBottomPanel::bottom("status_bar").show(egui_ctx, |ui|
+    {
+        ui.horizontal(|ui|
+        {
+            let (x, y) = cursor;
+            match pixels.get(y.max(0) as usize).and_then(|row| row.get(x.max(0) as usize)).filter(|_| x >= 0 && y >= 0)
+            {
+                Some(&color) =>
+                {
+                    ui.label(format!("{}, {}", x, y));
+                    palette::swatch(ui, Color::from_u8(color), 14.0);
+                    ui.label(format!("#{:02X}{:02X}{:02X}{:02X}", color.0, color.1, color.2, color.3));
+                }
+                None =>
+                {
+                    ui.label("-, -");
+                }
+            }
+            ui.separator();
+
+            if let Some(s) = selection
+            {
+                match s.bounds()
+                {
+                    Some((x0, y0, x1, y1)) => ui.label(format!("Selection {}x{} at {}, {} ({} pixels)", x1 - x0, y1 - y0, x0, y0, s.count())),
+                    None => ui.label("Selection empty"),
+                };
+                ui.separator();
+            }
+
+            ui.label(format!("{:.0}%", viewport.percent()));
+            ui.separator();
+            ui.label(format!("{}x{}", canvas.0, canvas.1));
+        });
+    });
+}
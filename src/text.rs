@@ -0,0 +1,154 @@
+use crate::color::Color;
+use crate::document::Document;
+use crate::font::{self, Align, BitmapFont};
+use crate::selection::{self, Selection};
+
+//Clicking on the canvas writes the text there with the current colour
+//The text and fonts are shared by all documents, whether the tool is on is part of ToolState
+pub struct TextTool
+{
+    pub text: String,
+    pub fonts: Vec<BitmapFont>,
+    pub font: usize,
+    pub align: Align,
+    pub spacing: i32,//Extra pixels between letters, can be negative
+    pub line_spacing: i32,
+    pub select: bool,//Selects the written pixels, so they can be transformed afterwards
+    pub path: String,
+    pub cell: (u32, u32),
+    pub chars: String,
+}
+
+impl Default for TextTool
+{
+    fn default() -> Self
+    {
+        TextTool
+        {
+            text: "TEXT".to_string(),
+            fonts: vec![BitmapFont::builtin()],
+            font: 0,
+            align: Align::Left,
+            spacing: 0,
+            line_spacing: 1,
+            select: false,
+            path: String::new(),
+            cell: (8, 8),
+            chars: (' '..='~').collect(),
+        }
+    }
+}
+
+impl TextTool
+{
+    pub fn current_font(&self) -> &BitmapFont
+    {
+        &self.fonts[self.font.min(self.fonts.len() - 1)]
+    }
+
+    pub fn render(&self) -> Vec<Vec<bool>>
+    {
+        font::render_text(self.current_font(), &self.text, self.align, self.spacing, self.line_spacing)
+    }
+}
+
+impl Document
+{
+    //Anchor is the top left, top middle or top right of the text depending on the alignment
+    pub fn stamp_text(&mut self, tool: &TextTool, anchor: (i32, i32), color: Color)
+    {
+        let mask = tool.render();
+        let width = mask.first().map(|r| r.len()).unwrap_or(0) as i32;
+        let x0 = match tool.align
+        {
+            Align::Left => anchor.0,
+            Align::Center => anchor.0 - width / 2,
+            Align::Right => anchor.0 - width + 1,
+        };
+
+        self.checkpoint();
+        let mut written = Selection::new(self.width as usize, self.height as usize);
+        for (my, row) in mask.iter().enumerate()
+        {
+            for (mx, set) in row.iter().enumerate()
+            {
+                let (x, y) = (x0 + mx as i32, anchor.1 + my as i32);
+                if *set && x >= 0 && y >= 0 && x < self.width && y < self.height && selection::editable(&self.selection, x, y)
+                {
+                    self.pixels[y as usize][x as usize] = color.to_u8();
+                    written.mask[y as usize * self.width as usize + x as usize] = true;
+                }
+            }
+        }
+
+        if tool.select && written.count() > 0
+        {
+            self.selection = Some(written);
+        }
+        self.canvas.mark_all_dirty();
+    }
+}
+
+pub fn text_ui(ui: &mut egui::Ui, tool: &mut TextTool)
+{
+    ui.label("Click on the canvas to write");
+    ui.text_edit_multiline(&mut tool.text);
+
+    egui::ComboBox::from_id_source("font").selected_text(tool.current_font().name.clone()).show_ui(ui, |ui|
+    {
+        for (i, font) in tool.fonts.iter().enumerate()
+        {
+            ui.selectable_value(&mut tool.font, i, font.name.clone());
+        }
+    });
+    ui.horizontal(|ui|
+    {
+        ui.radio_value(&mut tool.align, Align::Left, "Left");
+        ui.radio_value(&mut tool.align, Align::Center, "Center");
+        ui.radio_value(&mut tool.align, Align::Right, "Right");
+    });
+    ui.horizontal(|ui|
+    {
+        ui.add(egui::DragValue::new(&mut tool.spacing).clamp_range(-8..=32).prefix("letters "));
+        ui.add(egui::DragValue::new(&mut tool.line_spacing).clamp_range(-8..=32).prefix("lines "));
+    });
+    ui.checkbox(&mut tool.select, "Select written pixels");
+
+    ui.collapsing("Load Font", |ui|
+    {
+        ui.label("Path: (with ending)");
+        ui.text_edit_singleline(&mut tool.path);
+        if ui.button("Load BDF").clicked()
+        {
+            match std::fs::read_to_string(&tool.path).ok().and_then(|text| BitmapFont::from_bdf(&tool.path, &text))
+            {
+                Some(font) =>
+                {
+                    tool.fonts.push(font);
+                    tool.font = tool.fonts.len() - 1;
+                }
+                None => println!("Was not able to load font {}", tool.path),
+            }
+        }
+
+        ui.label("Glyph sheet: cell size and characters in order");
+        ui.horizontal(|ui|
+        {
+            ui.add(egui::DragValue::new(&mut tool.cell.0).clamp_range(1..=64).prefix("w "));
+            ui.add(egui::DragValue::new(&mut tool.cell.1).clamp_range(1..=64).prefix("h "));
+        });
+        ui.text_edit_singleline(&mut tool.chars);
+        if ui.button("Load PNG Sheet").clicked()
+        {
+            match image::open(&tool.path)
+            {
+                Ok(img) =>
+                {
+                    tool.fonts.push(BitmapFont::from_sheet(&tool.path, &img.to_rgba8(), tool.cell, &tool.chars));
+                    tool.font = tool.fonts.len() - 1;
+                }
+                Err(_) => println!("Was not able to load font {}", tool.path),
+            }
+        }
+    });
+}
@@ -0,0 +1,981 @@
+use crate::color::Color;
+use crate::document::Document;
+use crate::selection::Selection;
+
+type Pixels = Vec<Vec<(u8, u8, u8, u8)>>;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ScaleMethod
+{
+    Nearest,
+    Scale2x,
+    Scale3x,
+    Xbr,
+    RotSprite,
+}
+
+impl ScaleMethod
+{
+    pub const ALL: [ScaleMethod; 5] = [ScaleMethod::Nearest, ScaleMethod::Scale2x, ScaleMethod::Scale3x, ScaleMethod::Xbr, ScaleMethod::RotSprite];
+
+    pub fn name(self) -> &'static str
+    {
+        match self
+        {
+            ScaleMethod::Nearest => "Nearest Neighbour",
+            ScaleMethod::Scale2x => "Scale2x (EPX)",
+            ScaleMethod::Scale3x => "Scale3x",
+            ScaleMethod::Xbr => "xBR 2x",
+            ScaleMethod::RotSprite => "RotSprite (smooth)",
+        }
+    }
+
+    //Fixed factor, None when any size works
+    pub fn factor(self) -> Option<i32>
+    {
+        match self
+        {
+            ScaleMethod::Scale2x | ScaleMethod::Xbr => Some(2),
+            ScaleMethod::Scale3x => Some(3),
+            ScaleMethod::Nearest | ScaleMethod::RotSprite => None,
+        }
+    }
+
+    //Size of the result, None when it is larger than MAX_SIZE or the input too large for the 8x RotSprite image
+    pub fn result_size(self, size: (i32, i32), target: (i32, i32)) -> Option<(i32, i32)>
+    {
+        let result = match self.factor()
+        {
+            Some(factor) => (size.0.checked_mul(factor)?, size.1.checked_mul(factor)?),
+            None => target,
+        };
+        let input_fits = self != ScaleMethod::RotSprite || (size.0 <= ROTSPRITE_MAX_INPUT && size.1 <= ROTSPRITE_MAX_INPUT);
+        let result_fits = (1..=MAX_SIZE).contains(&result.0) && (1..=MAX_SIZE).contains(&result.1);
+        if input_fits && result_fits { Some(result) } else { None }
+    }
+}
+
+//Largest document the size dialogs allow
+pub const MAX_SIZE: i32 = 4096;
+//RotSprite works on an 8x copy, this keeps it at MAX_SIZE
+const ROTSPRITE_MAX_INPUT: i32 = MAX_SIZE / 8;
+
+fn size(pixels: &Pixels) -> (i32, i32)
+{
+    let height = pixels.len() as i32;
+    let width = if height > 0 { pixels[0].len() as i32 } else { 0 };
+    (width, height)
+}
+
+//Pixel with coordinates clamped to the image, so the borders repeat
+fn clamped(pixels: &Pixels, x: i32, y: i32) -> (u8, u8, u8, u8)
+{
+    let (width, height) = size(pixels);
+    pixels[y.clamp(0, height - 1) as usize][x.clamp(0, width - 1) as usize]
+}
+
+//anchor is (0..=2, 0..=2), (0, 0) keeps the top left corner in place and (1, 1) the center
+pub fn resize_canvas(pixels: &Pixels, width: i32, height: i32, anchor: (i32, i32)) -> (Pixels, (i32, i32))
+{
+    let (old_width, old_height) = size(pixels);
+    let shift = ((width - old_width) * anchor.0 / 2, (height - old_height) * anchor.1 / 2);
+
+    let mut result = vec![vec![(0, 0, 0, 0); width as usize]; height as usize];
+    for (y, row) in result.iter_mut().enumerate()
+    {
+        for (x, pixel) in row.iter_mut().enumerate()
+        {
+            let (sx, sy) = (x as i32 - shift.0, y as i32 - shift.1);
+            if sx >= 0 && sy >= 0 && sx < old_width && sy < old_height
+            {
+                *pixel = pixels[sy as usize][sx as usize];
+            }
+        }
+    }
+
+    (result, shift)
+}
+
+pub fn scale_nearest(pixels: &Pixels, width: i32, height: i32) -> Pixels
+{
+    let (old_width, old_height) = size(pixels);
+
+    (0..height).map(|y|
+    {
+        let sy = (y * old_height / height) as usize;
+        (0..width).map(|x| pixels[sy][(x * old_width / width) as usize]).collect()
+    }).collect()
+}
+
+//EPX / AdvMAME2x: each pixel becomes 2x2, corners take a neighbour colour where two edges meet
+pub fn scale2x(pixels: &Pixels) -> Pixels
+{
+    let (width, height) = size(pixels);
+    let mut result = vec![vec![(0, 0, 0, 0); width as usize * 2]; height as usize * 2];
+
+    for y in 0..height
+    {
+        for x in 0..width
+        {
+            let e = clamped(pixels, x, y);
+            let b = clamped(pixels, x, y - 1);
+            let d = clamped(pixels, x - 1, y);
+            let f = clamped(pixels, x + 1, y);
+            let h = clamped(pixels, x, y + 1);
+
+            let (x, y) = (x as usize * 2, y as usize * 2);
+            if b != h && d != f
+            {
+                result[y][x] = if d == b { d } else { e };
+                result[y][x + 1] = if b == f { f } else { e };
+                result[y + 1][x] = if d == h { d } else { e };
+                result[y + 1][x + 1] = if h == f { f } else { e };
+            }
+            else
+            {
+                result[y][x] = e;
+                result[y][x + 1] = e;
+                result[y + 1][x] = e;
+                result[y + 1][x + 1] = e;
+            }
+        }
+    }
+
+    result
+}
+
+//AdvMAME3x
+pub fn scale3x(pixels: &Pixels) -> Pixels
+{
+    let (width, height) = size(pixels);
+    let mut result = vec![vec![(0, 0, 0, 0); width as usize * 3]; height as usize * 3];
+
+    for y in 0..height
+    {
+        for x in 0..width
+        {
+            let p = |dx: i32, dy: i32| clamped(pixels, x + dx, y + dy);
+            let (a, b, c) = (p(-1, -1), p(0, -1), p(1, -1));
+            let (d, e, f) = (p(-1, 0), p(0, 0), p(1, 0));
+            let (g, h, i) = (p(-1, 1), p(0, 1), p(1, 1));
+
+            let mut block = [e; 9];
+            if b != h && d != f
+            {
+                block[0] = if d == b { d } else { e };
+                block[1] = if (d == b && e != c) || (b == f && e != a) { b } else { e };
+                block[2] = if b == f { f } else { e };
+                block[3] = if (d == b && e != g) || (d == h && e != a) { d } else { e };
+                block[5] = if (b == f && e != i) || (h == f && e != c) { f } else { e };
+                block[6] = if d == h { d } else { e };
+                block[7] = if (d == h && e != i) || (h == f && e != g) { h } else { e };
+                block[8] = if h == f { f } else { e };
+            }
+
+            for (n, pixel) in block.iter().enumerate()
+            {
+                result[y as usize * 3 + n / 3][x as usize * 3 + n % 3] = *pixel;
+            }
+        }
+    }
+
+    result
+}
+
+//xBR level 1 at 2x, without blending so no new colours appear
+//Each corner of a pixel looks at a 5x5 area to decide if an edge runs across it
+pub fn xbr2x(pixels: &Pixels) -> Pixels
+{
+    let (width, height) = size(pixels);
+    let lab: Vec<Vec<[f32; 4]>> = pixels.iter().map(|row| row.iter().map(|p| Color::from_u8(*p).to_oklab()).collect()).collect();
+    let mut result = vec![vec![(0, 0, 0, 0); width as usize * 2]; height as usize * 2];
+
+    for y in 0..height
+    {
+        for x in 0..width
+        {
+            for (sx, sy) in [(-1, -1), (1, -1), (-1, 1), (1, 1)]
+            {
+                //Mirrored so the corner always is the bottom right one
+                let p = |dx: i32, dy: i32| (x + dx * sx, y + dy * sy);
+                let dist = |a: (i32, i32), b: (i32, i32)|
+                {
+                    let (a, b) = (lab[a.1.clamp(0, height - 1) as usize][a.0.clamp(0, width - 1) as usize], lab[b.1.clamp(0, height - 1) as usize][b.0.clamp(0, width - 1) as usize]);
+                    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2) + (a[3] - b[3]).powi(2)).sqrt()
+                };
+
+                let (e, f, h, i) = (p(0, 0), p(1, 0), p(0, 1), p(1, 1));
+                let edge = dist(e, p(1, -1)) + dist(e, p(-1, 1)) + dist(i, p(2, 0)) + dist(i, p(0, 2)) + 4.0 * dist(h, f);
+                let across = dist(h, p(-1, 0)) + dist(h, p(1, 2)) + dist(f, p(2, 1)) + dist(f, p(0, -1)) + 4.0 * dist(e, i);
+
+                let color = if edge < across && dist(e, f) > 0.0 && dist(e, h) > 0.0
+                {
+                    let closer = if dist(e, f) <= dist(e, h) { f } else { h };
+                    clamped(pixels, closer.0, closer.1)
+                }
+                else
+                {
+                    pixels[y as usize][x as usize]
+                };
+
+                let ox = (x * 2 + (sx + 1) / 2) as usize;
+                let oy = (y * 2 + (sy + 1) / 2) as usize;
+                result[oy][ox] = color;
+            }
+        }
+    }
+
+    result
+}
+
+//Scale2x three times (8x) keeps the edges smooth, then it is sampled down to the wanted size
+pub fn rotsprite_scale(pixels: &Pixels, width: i32, height: i32) -> Pixels
+{
+    scale_nearest(&scale2x(&scale2x(&scale2x(pixels))), width, height)
+}
+
+pub fn scale_image(pixels: &Pixels, method: ScaleMethod, width: i32, height: i32) -> Pixels
+{
+    match method
+    {
+        ScaleMethod::Nearest => scale_nearest(pixels, width, height),
+        ScaleMethod::Scale2x => scale2x(pixels),
+        ScaleMethod::Scale3x => scale3x(pixels),
+        ScaleMethod::Xbr => xbr2x(pixels),
+        ScaleMethod::RotSprite => rotsprite_scale(pixels, width, height),
+    }
+}
+
+//Values in the Canvas Size and Scale Image sections, follow the document size until edited
+pub struct SizeDialog
+{
+    document_size: (i32, i32),
+    pub canvas_size: (i32, i32),
+    pub anchor: (i32, i32),
+    pub scale_size: (i32, i32),
+    pub keep_aspect: bool,
+    pub method: ScaleMethod,
+}
+
+impl Default for SizeDialog
+{
+    fn default() -> Self
+    {
+        SizeDialog
+        {
+            document_size: (0, 0),
+            canvas_size: (0, 0),
+            anchor: (1, 1),
+            scale_size: (0, 0),
+            keep_aspect: true,
+            method: ScaleMethod::Nearest,
+        }
+    }
+}
+
+impl Document
+{
+    pub fn resize_canvas(&mut self, width: i32, height: i32, anchor: (i32, i32))
+    {
+        self.checkpoint();
+        let (pixels, shift) = resize_canvas(&self.pixels, width, height, anchor);
+        self.pixels = pixels;
+        self.width = width;
+        self.height = height;
+        self.selection = None;
+
+        //The old pixels stay where they were on screen, references move along with them
+        self.viewport.pan(-shift.0 as f32 * self.viewport.scale, -shift.1 as f32 * self.viewport.scale);
+        for reference in self.references.iter_mut()
+        {
+            reference.position = (reference.position.0 + shift.0 as f32, reference.position.1 + shift.1 as f32);
+        }
+        self.canvas.mark_all_dirty();
+    }
+
+    //Does nothing when the result would be too large, see ScaleMethod::result_size
+    pub fn scale_image(&mut self, method: ScaleMethod, width: i32, height: i32)
+    {
+        if method.result_size((self.width, self.height), (width, height)).is_none()
+        {
+            println!("Was not able to scale the image, the result would be too large");
+            return;
+        }
+        self.checkpoint();
+        self.pixels = scale_image(&self.pixels, method, width, height);
+        let (new_width, new_height) = size(&self.pixels);
+        let factor = new_width as f32 / self.width as f32;
+        self.width = new_width;
+        self.height = new_height;
+        self.selection = None;
+
+        //Same size on screen as before
+        let origin = self.viewport.canvas_to_screen((0.0, 0.0));
+        self.viewport.zoom_at(self.viewport.scale / factor, origin);
+        self.canvas.mark_all_dirty();
+    }
+}
+
+pub fn size_ui(ui: &mut egui::Ui, dialog: &mut SizeDialog, doc: &mut Document)
+{
+    if dialog.document_size != (doc.width, doc.height)
+    {
+        dialog.document_size = (doc.width, doc.height);
+        dialog.canvas_size = dialog.document_size;
+        dialog.scale_size = dialog.document_size;
+    }
+
+    ui.collapsing("Canvas Size", |ui|
+    {
+        ui.horizontal(|ui|
+        {
+            ui.add(egui::DragValue::new(&mut dialog.canvas_size.0).clamp_range(1..=MAX_SIZE).prefix("w "));
+            ui.add(egui::DragValue::new(&mut dialog.canvas_size.1).clamp_range(1..=MAX_SIZE).prefix("h "));
+        });
+        egui::Grid::new("anchor").show(ui, |ui|
+        {
+            for y in 0..3
+            {
+                for x in 0..3
+                {
+                    ui.radio_value(&mut dialog.anchor, (x, y), "");
+                }
+                ui.end_row();
+            }
+        });
+        if ui.button("Apply").clicked() && dialog.canvas_size != (doc.width, doc.height)
+        {
+            doc.resize_canvas(dialog.canvas_size.0, dialog.canvas_size.1, dialog.anchor);
+        }
+    });
+
+    ui.collapsing("Scale Image", |ui|
+    {
+        egui::ComboBox::from_id_source("scale_method").selected_text(dialog.method.name()).show_ui(ui, |ui|
+        {
+            for method in ScaleMethod::ALL
+            {
+                ui.selectable_value(&mut dialog.method, method, method.name());
+            }
+        });
+
+        match dialog.method.factor()
+        {
+            Some(factor) =>
+            {
+                ui.label(format!("{}x{} -> {}x{}", doc.width, doc.height, doc.width * factor, doc.height * factor));
+            }
+            None =>
+            {
+                let before = dialog.scale_size;
+                ui.horizontal(|ui|
+                {
+                    ui.add(egui::DragValue::new(&mut dialog.scale_size.0).clamp_range(1..=MAX_SIZE).prefix("w "));
+                    ui.add(egui::DragValue::new(&mut dialog.scale_size.1).clamp_range(1..=MAX_SIZE).prefix("h "));
+                });
+                ui.checkbox(&mut dialog.keep_aspect, "Keep aspect ratio");
+                if dialog.keep_aspect
+                {
+                    if dialog.scale_size.0 != before.0
+                    {
+                        dialog.scale_size.1 = (dialog.scale_size.0 * doc.height / doc.width).clamp(1, MAX_SIZE);
+                    }
+                    else if dialog.scale_size.1 != before.1
+                    {
+                        dialog.scale_size.0 = (dialog.scale_size.1 * doc.width / doc.height).clamp(1, MAX_SIZE);
+                    }
+                }
+            }
+        }
+
+        let fits = dialog.method.result_size((doc.width, doc.height), dialog.scale_size).is_some();
+        if !fits
+        {
+            ui.label(format!("Too large, results are at most {0}x{0} and RotSprite takes at most {1}x{1}", MAX_SIZE, ROTSPRITE_MAX_INPUT));
+        }
+        if ui.add_enabled(fits, egui::Button::new("Apply")).clicked()
+        {
+            doc.scale_image(dialog.method, dialog.scale_size.0, dialog.scale_size.1);
+        }
+    });
+}
+
+pub fn flip<T: Copy>(grid: &[Vec<T>], horizontal: bool) -> Vec<Vec<T>>
+{
+    if horizontal
+    {
+        grid.iter().map(|row| row.iter().rev().copied().collect()).collect()
+    }
+    else
+    {
+        grid.iter().rev().cloned().collect()
+    }
+}
+
+//Clockwise quarter turns
+pub fn rotate<T: Copy>(grid: &[Vec<T>], turns: i32) -> Vec<Vec<T>>
+{
+    let mut result = grid.to_vec();
+    for _ in 0..turns.rem_euclid(4)
+    {
+        let height = result.len();
+        let width = if height > 0 { result[0].len() } else { 0 };
+        result = (0..width).map(|x| (0..height).rev().map(|y| result[y][x]).collect()).collect();
+    }
+    result
+}
+
+fn mask_rows(selection: &Selection) -> Vec<Vec<bool>>
+{
+    selection.mask.chunks(selection.width.max(1)).map(|row| row.to_vec()).collect()
+}
+
+fn mask_from_rows(rows: Vec<Vec<bool>>) -> Selection
+{
+    let height = rows.len();
+    let width = if height > 0 { rows[0].len() } else { 0 };
+    Selection
+    {
+        width,
+        height,
+        mask: rows.concat(),
+    }
+}
+
+//Selected pixels cut out of the image, (x, y) is the top left corner on the canvas
+struct Patch
+{
+    x: i32,
+    y: i32,
+    pixels: Pixels,
+    mask: Vec<Vec<bool>>,
+}
+
+impl Patch
+{
+    fn size(&self) -> (i32, i32)
+    {
+        size(&self.pixels)
+    }
+
+    //Same center as this patch, for results with a different size
+    fn recentered(&self, pixels: Pixels, mask: Vec<Vec<bool>>) -> Patch
+    {
+        let (width, height) = self.size();
+        let (new_width, new_height) = size(&pixels);
+        Patch
+        {
+            x: self.x + centering_offset(width, new_width),
+            y: self.y + centering_offset(height, new_height),
+            pixels,
+            mask,
+        }
+    }
+}
+
+//Shift of the start when a span changes length around the same middle
+//Odd differences can't be exact, growing rounds down and shrinking rounds up, so turning back always lands on the start again
+fn centering_offset(old: i32, new: i32) -> i32
+{
+    let difference = old - new;
+    if difference < 0
+    {
+        difference.div_euclid(2)
+    }
+    else
+    {
+        (difference + 1).div_euclid(2)
+    }
+}
+
+//Settings of the free transform, angle in degrees and skew as a shear factor
+pub struct FreeTransform
+{
+    pub angle: f32,
+    pub scale: (f32, f32),
+    pub skew: (f32, f32),
+}
+
+impl Default for FreeTransform
+{
+    fn default() -> Self
+    {
+        FreeTransform
+        {
+            angle: 0.0,
+            scale: (1.0, 1.0),
+            skew: (0.0, 0.0),
+        }
+    }
+}
+
+impl FreeTransform
+{
+    //2x2 matrix of rotation * skew * scale
+    fn matrix(&self) -> [f32; 4]
+    {
+        let (sin, cos) = self.angle.to_radians().sin_cos();
+        let rotation = [cos, -sin, sin, cos];
+        let skew = [1.0, self.skew.0, self.skew.1, 1.0];
+        let scale = [self.scale.0, 0.0, 0.0, self.scale.1];
+        mul(mul(rotation, skew), scale)
+    }
+}
+
+fn mul(a: [f32; 4], b: [f32; 4]) -> [f32; 4]
+{
+    [a[0] * b[0] + a[1] * b[2], a[0] * b[1] + a[1] * b[3], a[2] * b[0] + a[3] * b[2], a[2] * b[1] + a[3] * b[3]]
+}
+
+fn apply(m: [f32; 4], p: (f32, f32)) -> (f32, f32)
+{
+    (m[0] * p.0 + m[1] * p.1, m[2] * p.0 + m[3] * p.1)
+}
+
+//RotSprite idea: Scale2x three times, then every target pixel samples the 8x image through the inverse transform,
+//which keeps lines one pixel wide and adds no new colours
+fn free_transform(patch: &Patch, transform: &FreeTransform) -> Option<Patch>
+{
+    let m = transform.matrix();
+    let det = m[0] * m[3] - m[1] * m[2];
+    if det.abs() < 1e-4
+    {
+        return None;
+    }
+    let inverse = [m[3] / det, -m[1] / det, -m[2] / det, m[0] / det];
+
+    let (width, height) = patch.size();
+    let half = (width as f32 / 2.0, height as f32 / 2.0);
+    let corners = [(-half.0, -half.1), (half.0, -half.1), (-half.0, half.1), (half.0, half.1)].map(|c| apply(m, c));
+    let min = corners.iter().fold((f32::MAX, f32::MAX), |a, c| (a.0.min(c.0), a.1.min(c.1)));
+    let max = corners.iter().fold((f32::MIN, f32::MIN), |a, c| (a.0.max(c.0), a.1.max(c.1)));
+    let new_size = (((max.0 - min.0).round() as i32).max(1), ((max.1 - min.1).round() as i32).max(1));
+    if width > ROTSPRITE_MAX_INPUT || height > ROTSPRITE_MAX_INPUT || new_size.0 > MAX_SIZE || new_size.1 > MAX_SIZE
+    {
+        return None;
+    }
+
+    let big = scale2x(&scale2x(&scale2x(&patch.pixels)));
+    let mut pixels = vec![vec![(0, 0, 0, 0); new_size.0 as usize]; new_size.1 as usize];
+    let mut mask = vec![vec![false; new_size.0 as usize]; new_size.1 as usize];
+
+    for y in 0..new_size.1
+    {
+        for x in 0..new_size.0
+        {
+            let target = (x as f32 + 0.5 - new_size.0 as f32 / 2.0, y as f32 + 0.5 - new_size.1 as f32 / 2.0);
+            let source = apply(inverse, target);
+            let source = (source.0 + half.0, source.1 + half.1);
+            if source.0 < 0.0 || source.1 < 0.0 || source.0 >= width as f32 || source.1 >= height as f32
+            {
+                continue;
+            }
+
+            if patch.mask[source.1 as usize][source.0 as usize]
+            {
+                pixels[y as usize][x as usize] = big[(source.1 * 8.0) as usize][(source.0 * 8.0) as usize];
+                mask[y as usize][x as usize] = true;
+            }
+        }
+    }
+
+    Some(patch.recentered(pixels, mask))
+}
+
+impl Document
+{
+    //Copy of the selected pixels, the image stays as it is
+    fn selected_patch(&self) -> Option<Patch>
+    {
+        let selection = self.selection.as_ref()?;
+        let (x0, y0, x1, y1) = selection.bounds()?;
+
+        let mut pixels = vec![vec![(0, 0, 0, 0); (x1 - x0) as usize]; (y1 - y0) as usize];
+        let mut mask = vec![vec![false; (x1 - x0) as usize]; (y1 - y0) as usize];
+        for y in y0..y1
+        {
+            for x in x0..x1
+            {
+                if selection.contains(x, y)
+                {
+                    let (px, py) = ((x - x0) as usize, (y - y0) as usize);
+                    pixels[py][px] = self.pixels[y as usize][x as usize];
+                    mask[py][px] = true;
+                }
+            }
+        }
+
+        Some(Patch
+        {
+            x: x0,
+            y: y0,
+            pixels,
+            mask,
+        })
+    }
+
+    //Pastes the patch over the image and selects it, parts outside of the canvas are lost
+    fn place(&mut self, patch: Patch)
+    {
+        let mut selection = Selection::new(self.width as usize, self.height as usize);
+        for (py, row) in patch.mask.iter().enumerate()
+        {
+            for (px, selected) in row.iter().enumerate()
+            {
+                let (x, y) = (patch.x + px as i32, patch.y + py as i32);
+                if *selected && x >= 0 && y >= 0 && x < self.width && y < self.height
+                {
+                    self.pixels[y as usize][x as usize] = patch.pixels[py][px];
+                    selection.mask[y as usize * self.width as usize + x as usize] = true;
+                }
+            }
+        }
+        self.selection = Some(selection);
+        self.canvas.mark_all_dirty();
+    }
+
+    fn transform_selection(&mut self, f: impl FnOnce(&Patch) -> Option<Patch>)
+    {
+        //No undo step when there is nothing selected or the transform gives nothing, e.g. a free transform that squashes the selection flat
+        let (patch, result) = match self.selected_patch().and_then(|patch| f(&patch).map(|result| (patch, result)))
+        {
+            Some(patches) => patches,
+            None => return,
+        };
+
+        //The old place of the selected pixels becomes transparent
+        self.checkpoint();
+        for (py, row) in patch.mask.iter().enumerate()
+        {
+            for (px, _) in row.iter().enumerate().filter(|(_, selected)| **selected)
+            {
+                self.pixels[(patch.y + py as i32) as usize][(patch.x + px as i32) as usize] = (0, 0, 0, 0);
+            }
+        }
+        self.place(result);
+    }
+
+    pub fn flip_image(&mut self, horizontal: bool)
+    {
+        self.checkpoint();
+        self.pixels = flip(&self.pixels, horizontal);
+        self.selection = self.selection.as_ref().map(|s| mask_from_rows(flip(&mask_rows(s), horizontal)));
+        self.canvas.mark_all_dirty();
+    }
+
+    pub fn rotate_image(&mut self, turns: i32)
+    {
+        self.checkpoint();
+        self.pixels = rotate(&self.pixels, turns);
+        self.selection = self.selection.as_ref().map(|s| mask_from_rows(rotate(&mask_rows(s), turns)));
+        if turns.rem_euclid(2) == 1
+        {
+            std::mem::swap(&mut self.width, &mut self.height);
+        }
+        self.canvas.mark_all_dirty();
+    }
+
+    pub fn flip_selection(&mut self, horizontal: bool)
+    {
+        self.transform_selection(|patch| Some(Patch
+        {
+            x: patch.x,
+            y: patch.y,
+            pixels: flip(&patch.pixels, horizontal),
+            mask: flip(&patch.mask, horizontal),
+        }));
+    }
+
+    pub fn rotate_selection(&mut self, turns: i32)
+    {
+        self.transform_selection(|patch| Some(patch.recentered(rotate(&patch.pixels, turns), rotate(&patch.mask, turns))));
+    }
+
+    pub fn free_transform(&mut self, transform: &FreeTransform)
+    {
+        self.transform_selection(|patch| free_transform(patch, transform));
+    }
+}
+
+pub fn transform_ui(ui: &mut egui::Ui, free: &mut FreeTransform, doc: &mut Document)
+{
+    ui.collapsing("Transform", |ui|
+    {
+        ui.label("Image");
+        ui.horizontal(|ui|
+        {
+            if ui.button("Flip H").clicked()
+            {
+                doc.flip_image(true);
+            }
+            if ui.button("Flip V").clicked()
+            {
+                doc.flip_image(false);
+            }
+            if ui.button("90°").clicked()
+            {
+                doc.rotate_image(1);
+            }
+            if ui.button("180°").clicked()
+            {
+                doc.rotate_image(2);
+            }
+            if ui.button("270°").clicked()
+            {
+                doc.rotate_image(3);
+            }
+        });
+
+        ui.add_enabled_ui(doc.selection.is_some(), |ui|
+        {
+            ui.label("Selection");
+            ui.horizontal(|ui|
+            {
+                if ui.button("Flip H").clicked()
+                {
+                    doc.flip_selection(true);
+                }
+                if ui.button("Flip V").clicked()
+                {
+                    doc.flip_selection(false);
+                }
+                if ui.button("90°").clicked()
+                {
+                    doc.rotate_selection(1);
+                }
+                if ui.button("180°").clicked()
+                {
+                    doc.rotate_selection(2);
+                }
+                if ui.button("270°").clicked()
+                {
+                    doc.rotate_selection(3);
+                }
+            });
+
+            ui.add(egui::Slider::new(&mut free.angle, -180.0..=180.0).text("Angle"));
+            ui.horizontal(|ui|
+            {
+                ui.add(egui::DragValue::new(&mut free.scale.0).clamp_range(0.05..=16.0).speed(0.01).prefix("scale x "));
+                ui.add(egui::DragValue::new(&mut free.scale.1).clamp_range(0.05..=16.0).speed(0.01).prefix("y "));
+            });
+            ui.horizontal(|ui|
+            {
+                ui.add(egui::DragValue::new(&mut free.skew.0).clamp_range(-4.0..=4.0).speed(0.01).prefix("skew x "));
+                ui.add(egui::DragValue::new(&mut free.skew.1).clamp_range(-4.0..=4.0).speed(0.01).prefix("y "));
+            });
+            ui.horizontal(|ui|
+            {
+                if ui.button("Apply Transform").clicked()
+                {
+                    doc.free_transform(free);
+                }
+                if ui.button("Reset").clicked()
+                {
+                    *free = FreeTransform::default();
+                }
+            });
+        });
+    });
+}
+
+//What counts as empty border for Trim
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TrimBackground
+{
+    Transparent,
+    Corner,//Colour of the top left pixel
+}
+
+pub struct TrimSettings
+{
+    pub background: TrimBackground,
+    pub on_export: bool,
+}
+
+impl Default for TrimSettings
+{
+    fn default() -> Self
+    {
+        TrimSettings
+        {
+            background: TrimBackground::Transparent,
+            on_export: false,
+        }
+    }
+}
+
+//Edges (x0, y0, x1, y1) around everything that is not background, None for an empty image
+pub fn trim_bounds(pixels: &Pixels, background: TrimBackground) -> Option<(i32, i32, i32, i32)>
+{
+    let corner = *pixels.first()?.first()?;
+    let empty = |p: (u8, u8, u8, u8)| p.3 == 0 || (background == TrimBackground::Corner && p == corner);
+
+    let mut bounds: Option<(i32, i32, i32, i32)> = None;
+    for (y, row) in pixels.iter().enumerate()
+    {
+        for (x, pixel) in row.iter().enumerate()
+        {
+            if !empty(*pixel)
+            {
+                let (x, y) = (x as i32, y as i32);
+                bounds = Some(match bounds
+                {
+                    Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x + 1), y1.max(y + 1)),
+                    None => (x, y, x + 1, y + 1),
+                });
+            }
+        }
+    }
+
+    bounds
+}
+
+pub fn crop<T: Copy>(grid: &[Vec<T>], bounds: (i32, i32, i32, i32)) -> Vec<Vec<T>>
+{
+    let (x0, y0, x1, y1) = bounds;
+    grid[y0 as usize..y1 as usize].iter().map(|row| row[x0 as usize..x1 as usize].to_vec()).collect()
+}
+
+impl Document
+{
+    pub fn crop(&mut self, bounds: (i32, i32, i32, i32))
+    {
+        let (x0, y0, x1, y1) = bounds;
+        if (x0, y0, x1, y1) == (0, 0, self.width, self.height) || x1 <= x0 || y1 <= y0
+        {
+            return;
+        }
+
+        self.checkpoint();
+        self.pixels = crop(&self.pixels, bounds);
+        self.selection = self.selection.as_ref().map(|s| mask_from_rows(crop(&mask_rows(s), bounds)));
+        self.width = x1 - x0;
+        self.height = y1 - y0;
+
+        //The kept pixels stay where they were on screen
+        self.viewport.pan(x0 as f32 * self.viewport.scale, y0 as f32 * self.viewport.scale);
+        for reference in self.references.iter_mut()
+        {
+            reference.position = (reference.position.0 - x0 as f32, reference.position.1 - y0 as f32);
+        }
+        self.canvas.mark_all_dirty();
+    }
+
+    pub fn crop_to_selection(&mut self)
+    {
+        if let Some(bounds) = self.selection.as_ref().and_then(|s| s.bounds())
+        {
+            self.crop(bounds);
+        }
+    }
+
+    pub fn trim(&mut self, background: TrimBackground)
+    {
+        if let Some(bounds) = trim_bounds(&self.pixels, background)
+        {
+            self.crop(bounds);
+        }
+    }
+}
+
+pub fn crop_ui(ui: &mut egui::Ui, trim: &mut TrimSettings, doc: &mut Document)
+{
+    ui.collapsing("Crop", |ui|
+    {
+        if ui.add_enabled(doc.selection.is_some(), egui::Button::new("Crop to Selection")).clicked()
+        {
+            doc.crop_to_selection();
+        }
+        ui.horizontal(|ui|
+        {
+            if ui.button("Trim").clicked()
+            {
+                doc.trim(trim.background);
+            }
+            ui.radio_value(&mut trim.background, TrimBackground::Transparent, "Transparent");
+            ui.radio_value(&mut trim.background, TrimBackground::Corner, "Corner colour");
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn patch(x: i32, y: i32, width: usize, height: usize) -> Patch
+    {
+        Patch
+        {
+            x,
+            y,
+            pixels: (0..height).map(|y| (0..width).map(|x| (x as u8, y as u8, 0, 255)).collect()).collect(),
+            mask: vec![vec![true; width]; height],
+        }
+    }
+
+    fn turned(patch: &Patch, turns: i32) -> Patch
+    {
+        patch.recentered(rotate(&patch.pixels, turns), rotate(&patch.mask, turns))
+    }
+
+    #[test]
+    fn quarter_turns_do_not_drift()
+    {
+        for (width, height) in [(2, 3), (3, 2), (1, 4), (5, 2), (4, 4), (3, 3)]
+        {
+            for (x, y) in [(0, 0), (7, -3), (-5, 11)]
+            {
+                let start = patch(x, y, width, height);
+                let mut current = turned(&start, 1);
+                for turn in 2..=8
+                {
+                    current = turned(&current, 1);
+                    //Two quarter turns are the same as one half turn, which keeps the bounds
+                    let direct = turned(&start, turn % 4);
+                    assert_eq!((current.x, current.y), (direct.x, direct.y), "{}x{} at {}, {} after {} turns", width, height, x, y, turn);
+                    assert!(current.pixels == direct.pixels);
+                }
+                assert_eq!((current.x, current.y), (x, y));
+                assert!(current.pixels == start.pixels);
+            }
+        }
+    }
+
+    #[test]
+    fn turning_back_returns()
+    {
+        let start = patch(0, 0, 2, 3);
+        let there = turned(&start, 1);
+        assert_eq!(there.size(), (3, 2));
+        let back = turned(&there, 3);
+        assert_eq!((back.x, back.y), (0, 0));
+        assert_eq!((turned(&start, 2).x, turned(&start, 2).y), (0, 0));
+    }
+
+    #[test]
+    fn scale_results_are_capped()
+    {
+        assert_eq!(ScaleMethod::Scale2x.result_size((2048, 100), (0, 0)), Some((4096, 200)));
+        assert_eq!(ScaleMethod::Scale2x.result_size((2049, 100), (0, 0)), None);
+        assert_eq!(ScaleMethod::Scale3x.result_size((40000, 40000), (0, 0)), None);
+        assert_eq!(ScaleMethod::Xbr.result_size((i32::MAX, 1), (0, 0)), None);
+        assert_eq!(ScaleMethod::Nearest.result_size((4096, 4096), (4096, 1)), Some((4096, 1)));
+        assert_eq!(ScaleMethod::Nearest.result_size((16, 16), (4097, 16)), None);
+        assert_eq!(ScaleMethod::RotSprite.result_size((512, 512), (4096, 4096)), Some((4096, 4096)));
+        assert_eq!(ScaleMethod::RotSprite.result_size((513, 16), (600, 16)), None);
+    }
+
+    #[test]
+    fn centering_offsets()
+    {
+        assert_eq!(centering_offset(4, 4), 0);
+        assert_eq!(centering_offset(4, 2), 1);
+        assert_eq!(centering_offset(2, 4), -1);
+        assert_eq!(centering_offset(3, 2), 1);
+        assert_eq!(centering_offset(2, 3), -1);
+        assert_eq!(centering_offset(5, 2), 2);
+        assert_eq!(centering_offset(2, 5), -2);
+    }
+}
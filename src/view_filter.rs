@@ -0,0 +1,97 @@
+use crate::color::Color;
+
+//Only changes how the canvas is drawn, the pixels stay untouched
+#[derive(PartialEq, Clone, Copy)]
+pub enum ViewFilter
+{
+    None,
+    Protanopia,
+    Deuteranopia,
+    Tritanopia,
+    Greyscale,
+    Inverted,
+}
+
+impl ViewFilter
+{
+    pub const ALL: [ViewFilter; 6] = [ViewFilter::None, ViewFilter::Protanopia, ViewFilter::Deuteranopia, ViewFilter::Tritanopia, ViewFilter::Greyscale, ViewFilter::Inverted];
+
+    pub fn name(self) -> &'static str
+    {
+        match self
+        {
+            ViewFilter::None => "None",
+            ViewFilter::Protanopia => "Protanopia",
+            ViewFilter::Deuteranopia => "Deuteranopia",
+            ViewFilter::Tritanopia => "Tritanopia",
+            ViewFilter::Greyscale => "Greyscale",
+            ViewFilter::Inverted => "Inverted",
+        }
+    }
+
+    pub fn apply(self, pixel: (u8, u8, u8, u8)) -> (u8, u8, u8, u8)
+    {
+        match self
+        {
+            ViewFilter::None => pixel,
+            ViewFilter::Protanopia => simulate(pixel, &PROTANOPIA),
+            ViewFilter::Deuteranopia => simulate(pixel, &DEUTERANOPIA),
+            ViewFilter::Tritanopia => simulate(pixel, &TRITANOPIA),
+            ViewFilter::Greyscale =>
+            {
+                //Perceived lightness, so the value contrast stays readable
+                let l = Color::from_u8(pixel).to_oklab()[0];
+                let grey = Color::from_oklab([l, 0.0, 0.0, 1.0]).to_u8().0;
+                (grey, grey, grey, pixel.3)
+            }
+            ViewFilter::Inverted => (255 - pixel.0, 255 - pixel.1, 255 - pixel.2, pixel.3),
+        }
+    }
+}
+
+//Machado, Oliveira and Fernandes (2009), severity 1.0, applied in linear rgb
+const PROTANOPIA: [[f32; 3]; 3] =
+[
+    [0.152286, 1.052583, -0.204868],
+    [0.114503, 0.786281, 0.099216],
+    [-0.003882, -0.048116, 1.051998],
+];
+
+const DEUTERANOPIA: [[f32; 3]; 3] =
+[
+    [0.367322, 0.860646, -0.227968],
+    [0.280085, 0.672501, 0.047413],
+    [-0.011820, 0.042940, 0.968881],
+];
+
+const TRITANOPIA: [[f32; 3]; 3] =
+[
+    [1.255528, -0.076749, -0.178779],
+    [-0.078411, 0.930809, 0.147602],
+    [0.004733, 0.691367, 0.303900],
+];
+
+fn simulate(pixel: (u8, u8, u8, u8), matrix: &[[f32; 3]; 3]) -> (u8, u8, u8, u8)
+{
+    let c = Color::from_u8(pixel).to_linear();
+    let mut out = [0.0; 4];
+    for (i, row) in matrix.iter().enumerate()
+    {
+        out[i] = (row[0] * c[0] + row[1] * c[1] + row[2] * c[2]).clamp(0.0, 1.0);
+    }
+    out[3] = c[3];
+
+    Color::from_linear(out).to_u8()
+}
+
+pub fn view_filter_ui(ui: &mut egui::Ui, filter: &mut ViewFilter, side_by_side: &mut bool)
+{
+    egui::ComboBox::from_label("View Filter").selected_text(filter.name()).show_ui(ui, |ui|
+    {
+        for f in ViewFilter::ALL
+        {
+            ui.selectable_value(filter, f, f.name());
+        }
+    });
+    ui.checkbox(side_by_side, "Side by side");
+}
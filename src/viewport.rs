@@ -0,0 +1,278 @@
+//Preset zoom levels, in screen pixels per image pixel
+pub const ZOOM_LEVELS: [f32; 17] = [0.125, 0.25, 0.5, 1.0, 2.0, 3.0, 4.0, 6.0, 8.0, 12.0, 16.0, 24.0, 32.0, 48.0, 64.0, 96.0, 128.0];
+
+pub const MIN_SCALE: f32 = 0.125;
+pub const MAX_SCALE: f32 = 256.0;
+
+//Maps between screen and canvas coordinates
+//screen = offset + canvas * scale, so scale 1.0 is 100% (1 image pixel = 1 screen pixel)
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Viewport
+{
+    pub offset: (f32, f32),//Screen position of the top left corner of the canvas
+    pub scale: f32,
+}
+
+impl Default for Viewport
+{
+    fn default() -> Self
+    {
+        Viewport
+        {
+            offset: (0.0, 0.0),
+            scale: 1.0,
+        }
+    }
+}
+
+impl Viewport
+{
+    pub fn screen_to_canvas(&self, pos: (f32, f32)) -> (f32, f32)
+    {
+        ((pos.0 - self.offset.0) / self.scale, (pos.1 - self.offset.1) / self.scale)
+    }
+
+    pub fn canvas_to_screen(&self, pos: (f32, f32)) -> (f32, f32)
+    {
+        (self.offset.0 + pos.0 * self.scale, self.offset.1 + pos.1 * self.scale)
+    }
+
+    //Pixel under a screen position, can be outside of the image
+    pub fn screen_to_pixel(&self, pos: (f32, f32)) -> (i32, i32)
+    {
+        let c = self.screen_to_canvas(pos);
+        (c.0.floor() as i32, c.1.floor() as i32)
+    }
+
+    //Keeps the canvas point under anchor (screen position) where it is
+    pub fn zoom_at(&mut self, scale: f32, anchor: (f32, f32))
+    {
+        let before = self.screen_to_canvas(anchor);
+        self.scale = scale.clamp(MIN_SCALE, MAX_SCALE);
+        self.offset = (anchor.0 - before.0 * self.scale, anchor.1 - before.1 * self.scale);
+    }
+
+    //Smooth zoom by any factor
+    pub fn zoom_by(&mut self, factor: f32, anchor: (f32, f32))
+    {
+        self.zoom_at(self.scale * factor, anchor);
+    }
+
+    //Next bigger preset level
+    pub fn zoom_in(&mut self, anchor: (f32, f32))
+    {
+        let next = ZOOM_LEVELS.iter().copied().find(|z| *z > self.scale * 1.001).unwrap_or(MAX_SCALE);
+        self.zoom_at(next, anchor);
+    }
+
+    //Next smaller preset level
+    pub fn zoom_out(&mut self, anchor: (f32, f32))
+    {
+        let next = ZOOM_LEVELS.iter().copied().rev().find(|z| *z < self.scale / 1.001).unwrap_or(MIN_SCALE);
+        self.zoom_at(next, anchor);
+    }
+
+    pub fn pan(&mut self, dx: f32, dy: f32)
+    {
+        self.offset = (self.offset.0 + dx, self.offset.1 + dy);
+    }
+
+    //Stops the canvas from leaving area (x, y, w, h), at least margin pixels of it stay visible
+    pub fn keep_visible(&mut self, canvas: (f32, f32), area: (f32, f32, f32, f32), margin: f32)
+    {
+        let size = (canvas.0 * self.scale, canvas.1 * self.scale);
+        let margin = (margin.min(size.0).min(area.2), margin.min(size.1).min(area.3));
+
+        self.offset.0 = self.offset.0.clamp(area.0 + margin.0 - size.0, area.0 + area.2 - margin.0);
+        self.offset.1 = self.offset.1.clamp(area.1 + margin.1 - size.1, area.1 + area.3 - margin.1);
+    }
+
+    //Centers the canvas in area (x, y, w, h)
+    pub fn center(&mut self, canvas: (f32, f32), area: (f32, f32, f32, f32))
+    {
+        self.offset = (area.0 + (area.2 - canvas.0 * self.scale) / 2.0, area.1 + (area.3 - canvas.1 * self.scale) / 2.0);
+    }
+
+    //Biggest zoom at which the whole canvas fits into area, with a small margin
+    pub fn fit(&mut self, canvas: (f32, f32), area: (f32, f32, f32, f32))
+    {
+        if canvas.0 <= 0.0 || canvas.1 <= 0.0
+        {
+            return;
+        }
+
+        self.scale = ((area.2 / canvas.0).min(area.3 / canvas.1) * 0.9).clamp(MIN_SCALE, MAX_SCALE);
+        self.center(canvas, area);
+    }
+
+    pub fn percent(&self) -> f32
+    {
+        self.scale * 100.0
+    }
+}
+
+//Zoom buttons and presets, anchored at the middle of area (x, y, w, h)
+pub fn zoom_ui(ui: &mut egui::Ui, viewport: &mut Viewport, canvas: (f32, f32), area: (f32, f32, f32, f32))
+{
+    let center = (area.0 + area.2 / 2.0, area.1 + area.3 / 2.0);
+
+    ui.horizontal(|ui|
+    {
+        if ui.button("-").clicked()
+        {
+            viewport.zoom_out(center);
+        }
+        egui::ComboBox::from_id_source("zoom_levels").selected_text(format!("{:.0}%", viewport.percent())).show_ui(ui, |ui|
+        {
+            for level in ZOOM_LEVELS
+            {
+                if ui.selectable_label(viewport.scale == level, format!("{}%", level * 100.0)).clicked()
+                {
+                    viewport.zoom_at(level, center);
+                }
+            }
+        });
+        if ui.button("+").clicked()
+        {
+            viewport.zoom_in(center);
+        }
+    });
+    ui.horizontal(|ui|
+    {
+        if ui.button("Fit").clicked()
+        {
+            viewport.fit(canvas, area);
+        }
+        if ui.button("100%").clicked()
+        {
+            viewport.zoom_at(1.0, center);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    const SCALES: [f32; 6] = [0.125, 0.5, 1.0, 3.0, 16.0, 256.0];
+    const OFFSETS: [(f32, f32); 4] = [(0.0, 0.0), (100.0, 50.0), (-37.5, 12.25), (-4000.0, -900.0)];
+    const POINTS: [(f32, f32); 5] = [(0.0, 0.0), (1.0, 1.0), (15.5, 7.25), (-3.0, -0.5), (511.0, 1023.75)];
+
+    fn close(a: (f32, f32), b: (f32, f32)) -> bool
+    {
+        (a.0 - b.0).abs() <= 1e-3 && (a.1 - b.1).abs() <= 1e-3
+    }
+
+    #[test]
+    fn screen_canvas_round_trip()
+    {
+        for scale in SCALES
+        {
+            for offset in OFFSETS
+            {
+                let viewport = Viewport { offset, scale };
+                for p in POINTS
+                {
+                    assert!(close(viewport.screen_to_canvas(viewport.canvas_to_screen(p)), p), "{:?} at {:?}", p, viewport);
+                    assert!(close(viewport.canvas_to_screen(viewport.screen_to_canvas(p)), p), "{:?} at {:?}", p, viewport);
+                }
+            }
+        }
+
+        //Powers of two and these offsets are exact in f32
+        let viewport = Viewport { offset: (100.0, 50.0), scale: 16.0 };
+        assert_eq!(viewport.screen_to_canvas(viewport.canvas_to_screen((15.5, 7.25))), (15.5, 7.25));
+    }
+
+    #[test]
+    fn screen_to_pixel_floors()
+    {
+        let viewport = Viewport { offset: (10.0, 10.0), scale: 4.0 };
+        assert_eq!(viewport.screen_to_pixel((10.0, 10.0)), (0, 0));
+        assert_eq!(viewport.screen_to_pixel((13.9, 13.9)), (0, 0));
+        assert_eq!(viewport.screen_to_pixel((14.0, 14.0)), (1, 1));
+        //Left of and above the canvas, truncation would give 0 here
+        assert_eq!(viewport.screen_to_pixel((9.0, 8.0)), (-1, -1));
+        assert_eq!(viewport.screen_to_pixel((2.0, 1.9)), (-2, -3));
+    }
+
+    #[test]
+    fn zoom_keeps_anchor()
+    {
+        for start in SCALES
+        {
+            for offset in OFFSETS
+            {
+                let anchor = (333.0, 222.0);
+                let mut viewport = Viewport { offset, scale: start };
+                let before = viewport.screen_to_canvas(anchor);
+
+                viewport.zoom_in(anchor);
+                assert!(close(viewport.screen_to_canvas(anchor), before));
+                viewport.zoom_by(1.37, anchor);
+                assert!(close(viewport.screen_to_canvas(anchor), before));
+                viewport.zoom_out(anchor);
+                assert!(close(viewport.screen_to_canvas(anchor), before));
+                assert!((MIN_SCALE..=MAX_SCALE).contains(&viewport.scale));
+            }
+        }
+    }
+
+    #[test]
+    fn zoom_is_clamped()
+    {
+        let mut viewport = Viewport::default();
+        viewport.zoom_at(10000.0, (0.0, 0.0));
+        assert_eq!(viewport.scale, MAX_SCALE);
+        viewport.zoom_at(0.0001, (0.0, 0.0));
+        assert_eq!(viewport.scale, MIN_SCALE);
+        viewport.zoom_out((0.0, 0.0));
+        assert_eq!(viewport.scale, MIN_SCALE);
+    }
+
+    #[test]
+    fn keep_visible_at_edges()
+    {
+        let area = (0.0, 0.0, 800.0, 600.0);
+        let canvas = (64.0, 32.0);
+
+        //Pushed far out to the left and top, margin pixels stay inside
+        let mut viewport = Viewport { offset: (-10000.0, -10000.0), scale: 2.0 };
+        viewport.keep_visible(canvas, area, 16.0);
+        assert_eq!(viewport.offset, (16.0 - 128.0, 16.0 - 64.0));
+
+        //Pushed out to the right and bottom
+        let mut viewport = Viewport { offset: (10000.0, 10000.0), scale: 2.0 };
+        viewport.keep_visible(canvas, area, 16.0);
+        assert_eq!(viewport.offset, (800.0 - 16.0, 600.0 - 16.0));
+
+        //Inside already, nothing moves
+        let mut viewport = Viewport { offset: (100.0, 100.0), scale: 2.0 };
+        viewport.keep_visible(canvas, area, 16.0);
+        assert_eq!(viewport.offset, (100.0, 100.0));
+
+        //Canvas smaller than the margin, it can't be pushed out completely
+        let mut viewport = Viewport { offset: (-10000.0, 10000.0), scale: 0.125 };
+        viewport.keep_visible(canvas, area, 16.0);
+        assert_eq!(viewport.offset, (0.0, 600.0 - 4.0));
+    }
+
+    #[test]
+    fn fit_centers_and_fills()
+    {
+        let mut viewport = Viewport::default();
+        viewport.fit((100.0, 50.0), (20.0, 10.0, 1000.0, 1000.0));
+        assert!((viewport.scale - 9.0).abs() < 1e-4);
+        let top_left = viewport.canvas_to_screen((0.0, 0.0));
+        let bottom_right = viewport.canvas_to_screen((100.0, 50.0));
+        assert!(close(((top_left.0 + bottom_right.0) / 2.0, (top_left.1 + bottom_right.1) / 2.0), (520.0, 510.0)));
+
+        //Huge and empty canvases
+        viewport.fit((1_000_000.0, 10.0), (0.0, 0.0, 100.0, 100.0));
+        assert_eq!(viewport.scale, MIN_SCALE);
+        let before = viewport;
+        viewport.fit((0.0, 10.0), (0.0, 0.0, 100.0, 100.0));
+        assert_eq!(viewport, before);
+    }
+}